@@ -0,0 +1,71 @@
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use coi_actix_sample::models::data::DataId;
+use coi_actix_sample::models::sort::Sort;
+use coi_actix_sample::repositories::repo::IRepository;
+use criterion::async_executor::AsyncExecutor;
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::future::Future;
+
+// Added on top of the seeded rows, so `search` and paging run against a table of a realistic size.
+const EXTRA_ROWS: usize = 10_000;
+const INSERT_BATCH: usize = 1_000;
+
+// Runs each iteration on an actix runtime, the same one the server and the integration tests use.
+#[derive(Clone, Copy)]
+struct ActixExecutor<'a>(&'a actix_rt::Runtime);
+
+impl AsyncExecutor for ActixExecutor<'_> {
+    fn block_on<T>(&self, future: impl Future<Output = T>) -> T {
+        self.0.block_on(future)
+    }
+}
+
+// Needs docker for the database container, like the ignored tests in tests/repository.rs.
+fn repository(c: &mut Criterion) {
+    let runtime = actix_rt::Runtime::new().expect("Failed to start runtime");
+    let executor = ActixExecutor(&runtime);
+    let db = runtime.block_on(common::start());
+    let repository = db
+        .container
+        .resolve::<dyn IRepository>("repository")
+        .expect("Failed to resolve repository");
+    runtime.block_on(async {
+        for start in (0..EXTRA_ROWS).step_by(INSERT_BATCH) {
+            let names = (start..start + INSERT_BATCH)
+                .map(|n| format!("Bench row {}", n))
+                .collect::<Vec<_>>();
+            repository
+                .insert_many(&names)
+                .await
+                .expect("Failed to insert bench rows");
+        }
+    });
+
+    let mut group = c.benchmark_group("repository");
+    group.bench_function("get", |b| {
+        b.to_async(executor)
+            .iter(|| async { repository.get(DataId::from(1), false).await.unwrap() })
+    });
+    group.bench_function("get_all", |b| {
+        b.to_async(executor).iter(|| async {
+            repository
+                .get_all(50, 0, Sort::default(), false)
+                .await
+                .unwrap()
+        })
+    });
+    group.bench_function("search", |b| {
+        b.to_async(executor)
+            .iter(|| async { repository.search("row 123").await.unwrap() })
+    });
+    group.finish();
+
+    // The container is removed on drop, which needs the runtime.
+    drop(repository);
+    runtime.block_on(async move { drop(db) });
+}
+
+criterion_group!(benches, repository);
+criterion_main!(benches);
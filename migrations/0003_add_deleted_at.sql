@@ -0,0 +1,2 @@
+ALTER TABLE data
+    ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
@@ -0,0 +1,10 @@
+-- Fails if the table already holds duplicate names; remove or rename them first. Skipped when the
+-- constraint exists, as on databases created by `cargo xtask init`.
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = 'data_name_key') THEN
+        ALTER TABLE data
+            ADD CONSTRAINT data_name_key UNIQUE (name);
+    END IF;
+END;
+$$;
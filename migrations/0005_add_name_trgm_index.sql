@@ -0,0 +1,3 @@
+-- Lets `name ILIKE '%term%'` searches use an index instead of scanning every row.
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+CREATE INDEX IF NOT EXISTS data_name_trgm_idx ON data USING GIN (name gin_trgm_ops);
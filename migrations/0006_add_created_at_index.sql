@@ -0,0 +1,2 @@
+-- Serves the newest-first ordering of `GET /api/v1/data/recent`.
+CREATE INDEX IF NOT EXISTS data_created_at_idx ON data (created_at DESC, id DESC);
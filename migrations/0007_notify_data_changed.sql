@@ -0,0 +1,19 @@
+-- Tells listening servers which row changed so they can drop it from their caches. `TRUNCATE`
+-- sends an empty payload, meaning every row.
+CREATE OR REPLACE FUNCTION notify_data_changed() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'TRUNCATE' THEN
+        PERFORM pg_notify('data_changed', '');
+    ELSE
+        PERFORM pg_notify('data_changed', OLD.id::text);
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS data_changed ON data;
+CREATE TRIGGER data_changed AFTER UPDATE OR DELETE ON data
+    FOR EACH ROW EXECUTE FUNCTION notify_data_changed();
+DROP TRIGGER IF EXISTS data_truncated ON data;
+CREATE TRIGGER data_truncated AFTER TRUNCATE ON data
+    FOR EACH STATEMENT EXECUTE FUNCTION notify_data_changed();
@@ -0,0 +1,12 @@
+-- Tells listening servers about new rows so they can stream them to `GET /api/v1/data/events`
+-- subscribers. Only the id is sent, since a payload is limited to 8000 bytes.
+CREATE OR REPLACE FUNCTION notify_data_inserted() RETURNS trigger AS $$
+BEGIN
+    PERFORM pg_notify('data_inserted', NEW.id::text);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS data_inserted ON data;
+CREATE TRIGGER data_inserted AFTER INSERT ON data
+    FOR EACH ROW EXECUTE FUNCTION notify_data_inserted();
@@ -0,0 +1,10 @@
+-- Enforces the 255-character limit the repositories check, for writes that bypass the API. Fails if
+-- the table already holds longer names; shorten them first.
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = 'data_name_length') THEN
+        ALTER TABLE data
+            ADD CONSTRAINT data_name_length CHECK (char_length(name) <= 255);
+    END IF;
+END;
+$$;
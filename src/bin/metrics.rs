@@ -0,0 +1,87 @@
+// Serves only `/metrics` and `/health`, for deployments that scrape metrics from a sidecar instead
+// of the API server. The pools and repository come from `coi_actix_sample::wiring`, the same as in
+// the main binary, so the two read `Config` the same way.
+use actix_web::{App, HttpServer};
+use coi::container;
+use coi_actix_sample::{
+    config::{Config, ConfigProvider},
+    logging,
+    metrics::{MetricsRegistry, MetricsRegistryProvider},
+    middleware::metrics::RequestMetrics,
+    middleware::request_id::RequestIdHeader,
+    postgres::AppTls,
+    repositories::slow_query::SlowQueryLogProvider,
+    routes,
+    services::health::{HealthServiceProvider, Readiness, ReadinessProvider},
+    services::metrics::MetricsServiceProvider,
+    wiring::{build_pools, drain_pools, repository_provider},
+};
+use std::sync::Arc;
+use tracing_actix_web::TracingLogger;
+
+#[actix_rt::main]
+async fn main() -> Result<(), String> {
+    let config = match Config::from_env() {
+        Ok(config) => Arc::new(config),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    logging::init(&config);
+
+    let pools = build_pools(&config);
+    // Only what `/health` and `/metrics` resolve, so the caches, listener and API services of the
+    // main binary are left out.
+    let container = container! {
+        config => ConfigProvider::new(config.clone()); singleton,
+        pool => pools.pool; singleton,
+        read_pool => pools.read_pool; singleton,
+        repository => repository_provider(&config); scoped,
+        slow_queries => SlowQueryLogProvider; singleton,
+        health => HealthServiceProvider; scoped,
+        readiness => ReadinessProvider; singleton,
+        metrics => MetricsServiceProvider::<AppTls>::new(); scoped,
+        registry => MetricsRegistryProvider; singleton,
+    };
+    let registry = container
+        .resolve::<MetricsRegistry>("registry")
+        .map_err(|e| format!("{}", e))?;
+    let readiness = container
+        .resolve::<Readiness>("readiness")
+        .map_err(|e| format!("{}", e))?;
+
+    let bind_addr = config.bind_addr;
+    let app_container = container.clone();
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(app_container.clone())
+            .wrap(RequestMetrics::new(registry.clone()))
+            .wrap(RequestIdHeader)
+            .wrap(TracingLogger::default())
+            .configure(routes::health::route_config)
+            .configure(routes::metrics::route_config)
+    })
+    .shutdown_timeout(config.shutdown_timeout)
+    .workers(config.workers)
+    .bind(bind_addr)
+    .map_err(|e| format!("{}", e))?;
+    tracing::info!(%bind_addr, "Metrics server listening");
+    let server = server.run();
+    let handle = server.handle();
+    let server = actix_rt::spawn(server);
+
+    if let Err(e) = pools.startup.run(&config).await {
+        handle.stop(false).await;
+        return Err(e);
+    }
+    readiness.mark_ready();
+    server
+        .await
+        .map_err(|e| format!("{}", e))?
+        .map_err(|e| format!("{}", e))?;
+
+    drain_pools(&container).await?;
+    drop(container);
+    Ok(())
+}
@@ -0,0 +1,774 @@
+use crate::dtos::case::JsonCase;
+use crate::postgres::{AppTls, BreakerPolicy, RetryPolicy};
+use actix_web::http::Uri;
+use coi::{Container, Inject, Provide};
+use mobc_postgres::tokio_postgres::config::SslMode;
+#[cfg(not(feature = "tls"))]
+use mobc_postgres::tokio_postgres::NoTls;
+use mobc_postgres::tokio_postgres::{Config as PgConfig, Error as PostgresError};
+#[cfg(feature = "tls")]
+use openssl::error::ErrorStack;
+#[cfg(feature = "tls")]
+use openssl::ssl::{SslConnector, SslMethod};
+#[cfg(feature = "tls")]
+use postgres_openssl::MakeTlsConnector;
+use std::env;
+use std::fmt;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing_subscriber::filter::{LevelFilter, ParseError};
+use tracing_subscriber::EnvFilter;
+
+const DEFAULT_HOST: &str = "127.0.0.1";
+const DEFAULT_PORT: u16 = 45432;
+const DEFAULT_USER: &str = "docker";
+const DEFAULT_PASSWORD: &str = "docker";
+const DEFAULT_DBNAME: &str = "docker";
+const DEFAULT_BIND_ADDR: &str = "0.0.0.0:8080";
+const DEFAULT_SHUTDOWN_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_DB_STARTUP_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_CACHE_TTL_SECS: u64 = 30;
+const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 60;
+const DEFAULT_IDEMPOTENCY_TTL_SECS: u64 = 24 * 60 * 60;
+const DEFAULT_POOL_MAX_OPEN: u64 = 20;
+const DEFAULT_POOL_MAX_IDLE: u64 = 2;
+const DEFAULT_POOL_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_POOL_MAX_LIFETIME_SECS: u64 = 30 * 60;
+const DEFAULT_POOL_IDLE_TIMEOUT_SECS: u64 = 10 * 60;
+const DEFAULT_POOL_RETRIES: u32 = 3;
+const DEFAULT_SLOW_QUERY_MS: u64 = 500;
+const DEFAULT_QUERY_TIMEOUT_MS: u64 = 30_000;
+const DEFAULT_POOL_RETRY_DELAY_MS: u64 = 50;
+const DEFAULT_CIRCUIT_BREAKER_THRESHOLD: u32 = 5;
+const DEFAULT_CIRCUIT_BREAKER_COOLDOWN_SECS: u64 = 10;
+const DEFAULT_LOG_BODIES_MAX_BYTES: usize = 4096;
+const DEFAULT_LOG_BODIES_REDACT: &str = "password,token,secret,authorization";
+const DEFAULT_LOG_FILTER: &str = "info,actix_server=debug,actix_web=debug";
+
+pub struct PoolConfig {
+    pub(crate) max_open: u64,
+    pub(crate) max_idle: u64,
+    pub(crate) timeout: Duration,
+    pub(crate) warmup: u64,
+    pub(crate) max_lifetime: Option<Duration>,
+    pub(crate) idle_timeout: Option<Duration>,
+}
+
+#[derive(Clone, Default)]
+pub struct BodyLogConfig {
+    pub(crate) redact: Vec<String>,
+    pub(crate) max_bytes: usize,
+}
+
+#[derive(Clone, Copy)]
+pub enum LogFormat {
+    Pretty,
+    Json,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Invalid {0}: {1}")]
+    InvalidUrl(&'static str, #[source] PostgresError),
+    #[error("Invalid {0} `{1}`: expected a port number")]
+    InvalidPort(&'static str, String),
+    #[error("Invalid {0} `{1}`: expected a non-negative integer")]
+    InvalidNumber(&'static str, String),
+    #[error("Invalid {0} `{1}`: expected true or false")]
+    InvalidFlag(&'static str, String),
+    #[error("Invalid BIND_ADDR `{0}`: expected an ip:port address")]
+    InvalidBindAddr(String),
+    #[error("Invalid WORKERS: must be at least 1")]
+    InvalidWorkers,
+    #[error("Invalid pool configuration: {0}")]
+    InvalidPool(&'static str),
+    #[error("Invalid JSON_CASE `{0}`: expected snake or camel")]
+    InvalidJsonCase(String),
+    #[error("Invalid LOG_LEVEL `{0}`: expected one of off, error, warn, info, debug, trace")]
+    InvalidLogLevel(String),
+    #[error("Invalid RUST_LOG `{0}`: {1}")]
+    InvalidLogFilter(String, #[source] ParseError),
+    #[error("Invalid LOG_FORMAT `{0}`: expected pretty or json")]
+    InvalidLogFormat(String),
+    #[error("Invalid CORS_ORIGINS entry `{0}`: expected an origin like https://app.example.com")]
+    InvalidCorsOrigin(String),
+    #[error("Invalid PGSSLMODE `{0}`: expected one of disable, prefer, require")]
+    InvalidSslMode(String),
+    #[cfg(feature = "tls")]
+    #[error("TLS error: {0}")]
+    Tls(#[from] ErrorStack),
+    #[cfg(not(feature = "tls"))]
+    #[error("PGSSLMODE=require needs a build with the `tls` feature")]
+    TlsDisabled,
+}
+
+// Every setting read from the environment, parsed and validated once at startup. It is registered
+// in the container as `config` for the providers that need it.
+#[derive(Inject)]
+pub struct Config {
+    /// `DATABASE_URL`, or the `PGHOST`, `PGPORT`, `PGUSER`, `PGPASSWORD`, `PGDATABASE` and
+    /// `PGSSLMODE` variables
+    pub database: PgConfig,
+    /// `READ_DATABASE_URL`
+    pub read_database: Option<PgConfig>,
+    /// Built for `PGSSLMODE=require`, with `PGSSLROOTCERT`
+    pub tls: AppTls,
+    /// `POOL_MAX_OPEN`, `POOL_MAX_IDLE`, `POOL_TIMEOUT_SECS`, `POOL_WARMUP`,
+    /// `POOL_MAX_LIFETIME_SECS` and `POOL_IDLE_TIMEOUT_SECS`
+    pub pool: PoolConfig,
+    /// `POOL_RETRIES` and `POOL_RETRY_DELAY_MS`
+    pub pool_retry: RetryPolicy,
+    /// `CIRCUIT_BREAKER_THRESHOLD` and `CIRCUIT_BREAKER_COOLDOWN_SECS`
+    pub pool_breaker: BreakerPolicy,
+    /// `DB_STARTUP_TIMEOUT_SECS`
+    pub db_startup_timeout: Duration,
+    /// `QUERY_TIMEOUT_MS`
+    pub query_timeout: Option<Duration>,
+    /// `SLOW_QUERY_MS`
+    pub slow_query_threshold: Duration,
+    /// `BIND_ADDR`
+    pub bind_addr: SocketAddr,
+    /// `WORKERS`
+    pub workers: usize,
+    /// `SHUTDOWN_TIMEOUT_SECS`
+    pub shutdown_timeout: u64,
+    /// `REQUEST_TIMEOUT_SECS`
+    pub request_timeout: Option<Duration>,
+    /// `COMPRESSION`
+    pub compression: bool,
+    /// `RATE_LIMIT_PER_SEC`
+    pub rate_limit: Option<u32>,
+    /// `CORS_ORIGINS`
+    pub cors_origins: Vec<String>,
+    /// `LOG_BODIES`, `LOG_BODIES_REDACT` and `LOG_BODIES_MAX_BYTES`
+    pub body_log: Option<BodyLogConfig>,
+    /// `RUST_LOG` or `LOG_LEVEL`
+    pub log_filter: String,
+    /// `LOG_FORMAT`
+    pub log_format: LogFormat,
+    /// `JSON_CASE`
+    pub json_case: JsonCase,
+    /// `ADMIN_TOKEN`
+    pub admin_token: Option<String>,
+    /// `WRITE_TOKEN`
+    pub write_token: Option<String>,
+    /// `CACHE_TTL_SECS`
+    pub cache_ttl: Duration,
+    /// `IDEMPOTENCY_TTL_SECS`
+    pub idempotency_ttl: Duration,
+}
+
+// Every invalid setting found by `Config::from_env`, so they can all be fixed in one go.
+#[derive(Debug)]
+pub struct Errors(Vec<Error>);
+
+impl fmt::Display for Errors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid configuration:")?;
+        for error in &self.0 {
+            write!(f, "\n  - {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Errors {}
+
+#[derive(Default)]
+struct Checker(Vec<Error>);
+
+impl Checker {
+    fn check<T>(&mut self, result: Result<T, Error>) -> Option<T> {
+        result.map_err(|e| self.0.push(e)).ok()
+    }
+}
+
+impl Config {
+    // Reads every setting rather than stopping at the first invalid one.
+    pub fn from_env() -> Result<Self, Errors> {
+        let mut checker = Checker::default();
+        let mut database = checker.check(database_config());
+        let mut read_database = checker.check(read_database_config());
+        let tls = match (database.as_mut(), read_database.as_mut()) {
+            (Some(database), Some(read_database)) => {
+                let use_tls = apply_ssl_mode(database, read_database.as_mut());
+                checker.check(tls_connector(use_tls))
+            }
+            _ => None,
+        };
+        let pool = checker.check(pool_config());
+        let pool_retry = checker.check(pool_retry());
+        let pool_breaker = checker.check(pool_breaker());
+        let db_startup_timeout = checker.check(db_startup_timeout());
+        let query_timeout = checker.check(query_timeout());
+        if let (Some(database), Some(read_database), Some(Some(timeout))) =
+            (database.as_mut(), read_database.as_mut(), query_timeout)
+        {
+            apply_statement_timeout(database, read_database.as_mut(), timeout);
+        }
+        let slow_query_threshold = checker.check(slow_query_threshold());
+        let bind_addr = checker.check(bind_addr());
+        let workers = checker.check(workers());
+        let shutdown_timeout = checker.check(shutdown_timeout());
+        let request_timeout = checker.check(request_timeout());
+        let compression = checker.check(compression());
+        let rate_limit = checker.check(rate_limit());
+        let cors_origins = checker.check(cors_origins());
+        let body_log = checker.check(body_log());
+        let log_filter = checker.check(log_filter());
+        let log_format = checker.check(log_format());
+        let json_case = checker.check(json_case());
+        let cache_ttl = checker.check(cache_ttl());
+        let idempotency_ttl = checker.check(idempotency_ttl());
+        // Every value is present exactly when no error was recorded.
+        let config = (|| {
+            Some(Self {
+                database: database?,
+                read_database: read_database?,
+                tls: tls?,
+                pool: pool?,
+                pool_retry: pool_retry?,
+                pool_breaker: pool_breaker?,
+                db_startup_timeout: db_startup_timeout?,
+                query_timeout: query_timeout?,
+                slow_query_threshold: slow_query_threshold?,
+                bind_addr: bind_addr?,
+                workers: workers?,
+                shutdown_timeout: shutdown_timeout?,
+                request_timeout: request_timeout?,
+                compression: compression?,
+                rate_limit: rate_limit?,
+                cors_origins: cors_origins?,
+                body_log: body_log?,
+                log_filter: log_filter?,
+                log_format: log_format?,
+                json_case: json_case?,
+                admin_token: admin_token(),
+                write_token: write_token(),
+                cache_ttl: cache_ttl?,
+                idempotency_ttl: idempotency_ttl?,
+            })
+        })();
+        match config {
+            Some(config) if checker.0.is_empty() => Ok(config),
+            _ => Err(Errors(checker.0)),
+        }
+    }
+}
+
+pub struct ConfigProvider(Arc<Config>);
+
+impl ConfigProvider {
+    pub fn new(config: Arc<Config>) -> Self {
+        Self(config)
+    }
+}
+
+impl Provide for ConfigProvider {
+    type Output = Config;
+
+    fn provide(&self, _: &Container) -> coi::Result<Arc<Config>> {
+        Ok(self.0.clone())
+    }
+
+    #[cfg(feature = "debug")]
+    fn dependencies(&self) -> &'static [&'static str] {
+        &[]
+    }
+}
+
+fn var_or(key: &str, default: &str) -> String {
+    env::var(key).unwrap_or_else(|_| default.to_owned())
+}
+
+fn parse_var<T: FromStr>(key: &'static str, default: T) -> Result<T, Error> {
+    match env::var(key) {
+        Ok(value) => value.parse().map_err(|_| Error::InvalidNumber(key, value)),
+        Err(_) => Ok(default),
+    }
+}
+
+fn parse_flag(key: &'static str, default: bool) -> Result<bool, Error> {
+    match env::var(key) {
+        Ok(value) => match value.as_str() {
+            "1" | "true" => Ok(true),
+            "0" | "false" => Ok(false),
+            _ => Err(Error::InvalidFlag(key, value)),
+        },
+        Err(_) => Ok(default),
+    }
+}
+
+fn parse_list(key: &str, default: &str) -> Vec<String> {
+    var_or(key, default)
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+// `DATABASE_URL` takes precedence and may be either a `postgres://` url or a key=value connection
+// string. Otherwise the libpq-style `PG*` variables are read individually.
+fn database_config() -> Result<PgConfig, Error> {
+    if let Ok(url) = env::var("DATABASE_URL") {
+        return url
+            .parse()
+            .map_err(|e| Error::InvalidUrl("DATABASE_URL", e));
+    }
+
+    let port = match env::var("PGPORT") {
+        Ok(port) => port
+            .parse()
+            .map_err(|_| Error::InvalidPort("PGPORT", port))?,
+        Err(_) => DEFAULT_PORT,
+    };
+    let mut config = PgConfig::new();
+    config
+        .host(var_or("PGHOST", DEFAULT_HOST))
+        .port(port)
+        .user(var_or("PGUSER", DEFAULT_USER))
+        .password(var_or("PGPASSWORD", DEFAULT_PASSWORD))
+        .dbname(var_or("PGDATABASE", DEFAULT_DBNAME));
+    if let Ok(mode) = env::var("PGSSLMODE") {
+        config.ssl_mode(parse_ssl_mode(mode)?);
+    }
+    Ok(config)
+}
+
+// Reads are sent to `READ_DATABASE_URL` when it is set, and to the primary database otherwise.
+fn read_database_config() -> Result<Option<PgConfig>, Error> {
+    match env::var("READ_DATABASE_URL") {
+        Ok(url) => url
+            .parse()
+            .map(Some)
+            .map_err(|e| Error::InvalidUrl("READ_DATABASE_URL", e)),
+        Err(_) => Ok(None),
+    }
+}
+
+fn parse_ssl_mode(mode: String) -> Result<SslMode, Error> {
+    match mode.as_str() {
+        "disable" => Ok(SslMode::Disable),
+        "prefer" => Ok(SslMode::Prefer),
+        "require" => Ok(SslMode::Require),
+        _ => Err(Error::InvalidSslMode(mode)),
+    }
+}
+
+// Only `require` connects over TLS. Every other mode is turned into `disable`, so the connector
+// `AppTls` always provides isn't used opportunistically. The primary and the replica each keep their
+// own mode, and the connector is set up for TLS when either of them requires it.
+fn apply_ssl_mode(config: &mut PgConfig, read_config: Option<&mut PgConfig>) -> bool {
+    let mut use_tls = false;
+    for config in std::iter::once(config).chain(read_config) {
+        if config.get_ssl_mode() == SslMode::Require {
+            use_tls = true;
+        } else {
+            config.ssl_mode(SslMode::Disable);
+        }
+    }
+    use_tls
+}
+
+// Postgres cancels statements running past `QUERY_TIMEOUT_MS` itself, so one the client has given up
+// on doesn't keep its pooled connection busy. Appended to any `options` from the url, so it wins.
+fn apply_statement_timeout(
+    config: &mut PgConfig,
+    read_config: Option<&mut PgConfig>,
+    timeout: Duration,
+) {
+    let option = format!("-c statement_timeout={}", timeout.as_millis());
+    for config in std::iter::once(config).chain(read_config) {
+        let options = match config.get_options() {
+            Some(options) => format!("{} {}", options, option),
+            None => option.clone(),
+        };
+        config.options(&options);
+    }
+}
+
+// `PGSSLROOTCERT` can point to a CA bundle for servers whose certificates aren't signed by a
+// system-trusted authority.
+#[cfg(feature = "tls")]
+fn tls_connector(use_tls: bool) -> Result<AppTls, Error> {
+    let mut builder = SslConnector::builder(SslMethod::tls())?;
+    if let (true, Ok(path)) = (use_tls, env::var("PGSSLROOTCERT")) {
+        builder.set_ca_file(path)?;
+    }
+    Ok(MakeTlsConnector::new(builder.build()))
+}
+
+#[cfg(not(feature = "tls"))]
+fn tls_connector(use_tls: bool) -> Result<AppTls, Error> {
+    if use_tls {
+        return Err(Error::TlsDisabled);
+    }
+    Ok(NoTls)
+}
+
+fn bind_addr() -> Result<SocketAddr, Error> {
+    let addr = var_or("BIND_ADDR", DEFAULT_BIND_ADDR);
+    addr.parse().map_err(|_| Error::InvalidBindAddr(addr))
+}
+
+// Number of worker threads, defaulting to one per available CPU.
+fn workers() -> Result<usize, Error> {
+    let default = std::thread::available_parallelism().map_or(1, |n| n.get());
+    match parse_var("WORKERS", default)? {
+        0 => Err(Error::InvalidWorkers),
+        workers => Ok(workers),
+    }
+}
+
+// Bearer token required by the `/admin` routes. They reject every request when it is unset.
+fn admin_token() -> Option<String> {
+    env::var("ADMIN_TOKEN")
+        .ok()
+        .filter(|token| !token.is_empty())
+}
+
+// Bearer token required to change data through the `/data` routes. They are open when it is unset.
+fn write_token() -> Option<String> {
+    env::var("WRITE_TOKEN")
+        .ok()
+        .filter(|token| !token.is_empty())
+}
+
+// How long the server waits for in-flight requests to finish after receiving a shutdown signal.
+fn shutdown_timeout() -> Result<u64, Error> {
+    parse_var("SHUTDOWN_TIMEOUT_SECS", DEFAULT_SHUTDOWN_TIMEOUT_SECS)
+}
+
+// How long startup keeps retrying to reach the database, `0` tries once.
+fn db_startup_timeout() -> Result<Duration, Error> {
+    parse_var("DB_STARTUP_TIMEOUT_SECS", DEFAULT_DB_STARTUP_TIMEOUT_SECS).map(Duration::from_secs)
+}
+
+// How long `GET /api/v1/data/{id}` results stay in the in-memory cache.
+fn cache_ttl() -> Result<Duration, Error> {
+    parse_var("CACHE_TTL_SECS", DEFAULT_CACHE_TTL_SECS).map(Duration::from_secs)
+}
+
+// How long an `Idempotency-Key` sent to `POST /api/v1/data` is remembered.
+fn idempotency_ttl() -> Result<Duration, Error> {
+    parse_var("IDEMPOTENCY_TTL_SECS", DEFAULT_IDEMPOTENCY_TTL_SECS).map(Duration::from_secs)
+}
+
+fn pool_config() -> Result<PoolConfig, Error> {
+    let max_open = parse_var("POOL_MAX_OPEN", DEFAULT_POOL_MAX_OPEN)?;
+    // The default idle limit shrinks with small pools; only an explicit POOL_MAX_IDLE can conflict.
+    let max_idle = parse_var("POOL_MAX_IDLE", DEFAULT_POOL_MAX_IDLE.min(max_open))?;
+    let timeout = parse_var("POOL_TIMEOUT_SECS", DEFAULT_POOL_TIMEOUT_SECS)?;
+    // Connections beyond the idle limit would be closed again as soon as they're returned.
+    let warmup = parse_var("POOL_WARMUP", max_idle)?;
+    // Connections are recycled before the database or a proxy in between drops them. `0` keeps
+    // them forever.
+    let max_lifetime = parse_var("POOL_MAX_LIFETIME_SECS", DEFAULT_POOL_MAX_LIFETIME_SECS)?;
+    let idle_timeout = parse_var("POOL_IDLE_TIMEOUT_SECS", DEFAULT_POOL_IDLE_TIMEOUT_SECS)?;
+    if max_open == 0 {
+        return Err(Error::InvalidPool("POOL_MAX_OPEN must be at least 1"));
+    }
+    if max_idle > max_open {
+        return Err(Error::InvalidPool(
+            "POOL_MAX_IDLE must not be greater than POOL_MAX_OPEN",
+        ));
+    }
+    if timeout == 0 {
+        return Err(Error::InvalidPool("POOL_TIMEOUT_SECS must be at least 1"));
+    }
+    if warmup > max_idle {
+        return Err(Error::InvalidPool(
+            "POOL_WARMUP must not be greater than POOL_MAX_IDLE",
+        ));
+    }
+    Ok(PoolConfig {
+        max_open,
+        max_idle,
+        timeout: Duration::from_secs(timeout),
+        warmup,
+        max_lifetime: Some(Duration::from_secs(max_lifetime)).filter(|d| !d.is_zero()),
+        idle_timeout: Some(Duration::from_secs(idle_timeout)).filter(|d| !d.is_zero()),
+    })
+}
+
+// Requests allowed per second per client ip. Unset or `0` disables rate limiting.
+fn rate_limit() -> Result<Option<u32>, Error> {
+    parse_var("RATE_LIMIT_PER_SEC", 0).map(|rate| Some(rate).filter(|&rate| rate > 0))
+}
+
+// Origins allowed to make cross-origin requests, from the comma-separated `CORS_ORIGINS`. Empty
+// when unset, which leaves CORS disabled. Checked here because actix-cors panics on entries it can't
+// use, including `*`.
+fn cors_origins() -> Result<Vec<String>, Error> {
+    let origins = parse_list("CORS_ORIGINS", "");
+    match origins.iter().find(|origin| !is_origin(origin)) {
+        Some(origin) => Err(Error::InvalidCorsOrigin(origin.clone())),
+        None => Ok(origins),
+    }
+}
+
+// A scheme and host, with an optional port and nothing after it, as browsers send in `Origin`.
+fn is_origin(origin: &str) -> bool {
+    match origin.parse::<Uri>() {
+        Ok(uri) => {
+            uri.scheme().is_some()
+                && uri.authority().is_some()
+                && !origin.ends_with('/')
+                && uri.path_and_query().is_none_or(|path| path == "/")
+        }
+        Err(_) => false,
+    }
+}
+
+// Request and response bodies of the data routes are logged only when `LOG_BODIES` is set. Fields
+// named in `LOG_BODIES_REDACT` are masked, and each body is cut off after `LOG_BODIES_MAX_BYTES`.
+fn body_log() -> Result<Option<BodyLogConfig>, Error> {
+    if !parse_flag("LOG_BODIES", false)? {
+        return Ok(None);
+    }
+    Ok(Some(BodyLogConfig {
+        redact: parse_list("LOG_BODIES_REDACT", DEFAULT_LOG_BODIES_REDACT),
+        max_bytes: parse_var("LOG_BODIES_MAX_BYTES", DEFAULT_LOG_BODIES_MAX_BYTES)?,
+    }))
+}
+
+// How long a handler may run before the request fails with a `504`. `0` disables the timeout.
+fn request_timeout() -> Result<Option<Duration>, Error> {
+    parse_var("REQUEST_TIMEOUT_SECS", DEFAULT_REQUEST_TIMEOUT_SECS)
+        .map(|secs| Some(Duration::from_secs(secs)).filter(|timeout| !timeout.is_zero()))
+}
+
+// Whether responses are compressed for clients that send `Accept-Encoding`.
+fn compression() -> Result<bool, Error> {
+    parse_flag("COMPRESSION", true)
+}
+
+// Repository queries taking at least this long are logged as warnings.
+fn slow_query_threshold() -> Result<Duration, Error> {
+    parse_var("SLOW_QUERY_MS", DEFAULT_SLOW_QUERY_MS).map(Duration::from_millis)
+}
+
+// How long a single repository query may run before it fails with a `504`. `0` disables the
+// timeout.
+fn query_timeout() -> Result<Option<Duration>, Error> {
+    parse_var("QUERY_TIMEOUT_MS", DEFAULT_QUERY_TIMEOUT_MS)
+        .map(|ms| Some(Duration::from_millis(ms)).filter(|timeout| !timeout.is_zero()))
+}
+
+// How many times a failed pool checkout is retried, and the delay before the first retry.
+fn pool_retry() -> Result<RetryPolicy, Error> {
+    Ok(RetryPolicy {
+        retries: parse_var("POOL_RETRIES", DEFAULT_POOL_RETRIES)?,
+        base_delay: parse_var("POOL_RETRY_DELAY_MS", DEFAULT_POOL_RETRY_DELAY_MS)
+            .map(Duration::from_millis)?,
+    })
+}
+
+// How many failed pool checkouts in a row open the circuit breaker (`0` disables it), and how long
+// it stays open before the database is probed again.
+fn pool_breaker() -> Result<BreakerPolicy, Error> {
+    let threshold = parse_var(
+        "CIRCUIT_BREAKER_THRESHOLD",
+        DEFAULT_CIRCUIT_BREAKER_THRESHOLD,
+    )?;
+    let cooldown = parse_var(
+        "CIRCUIT_BREAKER_COOLDOWN_SECS",
+        DEFAULT_CIRCUIT_BREAKER_COOLDOWN_SECS,
+    )?;
+    if cooldown == 0 {
+        return Err(Error::InvalidPool(
+            "CIRCUIT_BREAKER_COOLDOWN_SECS must be at least 1",
+        ));
+    }
+    Ok(BreakerPolicy {
+        threshold,
+        cooldown: Duration::from_secs(cooldown),
+    })
+}
+
+// `RUST_LOG` takes precedence and accepts full filter directives. Otherwise everything is logged at
+// `LOG_LEVEL`, or at info with actix's server and request logs at debug when neither is set.
+fn log_filter() -> Result<String, Error> {
+    if let Ok(filter) = env::var(EnvFilter::DEFAULT_ENV) {
+        return match EnvFilter::try_new(&filter) {
+            Ok(_) => Ok(filter),
+            Err(e) => Err(Error::InvalidLogFilter(filter, e)),
+        };
+    }
+    match env::var("LOG_LEVEL") {
+        Ok(level) => level
+            .parse::<LevelFilter>()
+            .map(|level| level.to_string())
+            .map_err(|_| Error::InvalidLogLevel(level)),
+        Err(_) => Ok(DEFAULT_LOG_FILTER.to_owned()),
+    }
+}
+
+// One JSON object per line is what log aggregators expect, the default is for reading in a terminal.
+fn log_format() -> Result<LogFormat, Error> {
+    match env::var("LOG_FORMAT") {
+        Ok(format) => match format.as_str() {
+            "pretty" => Ok(LogFormat::Pretty),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(Error::InvalidLogFormat(format)),
+        },
+        Err(_) => Ok(LogFormat::Pretty),
+    }
+}
+
+// Field naming for JSON responses, `snake` (the default, matching the column names) or `camel`.
+fn json_case() -> Result<JsonCase, Error> {
+    match env::var("JSON_CASE") {
+        Ok(case) => match case.as_str() {
+            "snake" => Ok(JsonCase::Snake),
+            "camel" => Ok(JsonCase::Camel),
+            _ => Err(Error::InvalidJsonCase(case)),
+        },
+        Err(_) => Ok(JsonCase::Snake),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    // The environment is shared by every test thread, so tests that change it take turns.
+    static ENV: Mutex<()> = Mutex::new(());
+
+    fn from_env_with(vars: &[(&str, &str)]) -> Result<Config, Errors> {
+        let _guard = ENV.lock().unwrap_or_else(|e| e.into_inner());
+        for (key, value) in vars {
+            env::set_var(key, value);
+        }
+        let result = Config::from_env();
+        for (key, _) in vars {
+            env::remove_var(key);
+        }
+        result
+    }
+
+    #[test]
+    fn reports_every_invalid_variable() {
+        let errors = match from_env_with(&[
+            ("PGPORT", "not-a-port"),
+            ("WORKERS", "0"),
+            ("JSON_CASE", "kebab"),
+            ("LOG_FORMAT", "xml"),
+            ("COMPRESSION", "maybe"),
+            ("RUST_LOG", "coi_actix_sample=loud"),
+        ]) {
+            Ok(_) => panic!("invalid configuration was accepted"),
+            Err(errors) => errors,
+        };
+        assert_eq!(errors.0.len(), 6, "{}", errors);
+        assert!(errors
+            .0
+            .iter()
+            .any(|e| matches!(e, Error::InvalidPort("PGPORT", _))));
+        assert!(errors.0.iter().any(|e| matches!(e, Error::InvalidWorkers)));
+        assert!(errors
+            .0
+            .iter()
+            .any(|e| matches!(e, Error::InvalidJsonCase(_))));
+        assert!(errors
+            .0
+            .iter()
+            .any(|e| matches!(e, Error::InvalidLogFormat(_))));
+        assert!(errors
+            .0
+            .iter()
+            .any(|e| matches!(e, Error::InvalidFlag("COMPRESSION", _))));
+        assert!(errors
+            .0
+            .iter()
+            .any(|e| matches!(e, Error::InvalidLogFilter(_, _))));
+
+        let message = errors.to_string();
+        assert!(message.starts_with("Invalid configuration:"));
+        assert_eq!(message.lines().count(), 7);
+    }
+
+    fn pg_config(url: &str) -> PgConfig {
+        url.parse().unwrap()
+    }
+
+    #[test]
+    fn ssl_mode_is_decided_for_each_url() {
+        let mut primary = pg_config("postgres://docker@primary/docker");
+        let mut replica = pg_config("postgres://docker@replica/docker?sslmode=require");
+        assert!(apply_ssl_mode(&mut primary, Some(&mut replica)));
+        assert_eq!(primary.get_ssl_mode(), SslMode::Disable);
+        assert_eq!(replica.get_ssl_mode(), SslMode::Require);
+
+        let mut primary = pg_config("postgres://docker@primary/docker?sslmode=require");
+        let mut replica = pg_config("postgres://docker@replica/docker?sslmode=prefer");
+        assert!(apply_ssl_mode(&mut primary, Some(&mut replica)));
+        assert_eq!(primary.get_ssl_mode(), SslMode::Require);
+        assert_eq!(replica.get_ssl_mode(), SslMode::Disable);
+
+        let mut primary = pg_config("postgres://docker@primary/docker?sslmode=prefer");
+        assert!(!apply_ssl_mode(&mut primary, None));
+        assert_eq!(primary.get_ssl_mode(), SslMode::Disable);
+    }
+
+    #[test]
+    fn statement_timeout_is_set_for_each_url() {
+        let mut primary = pg_config("postgres://docker@primary/docker");
+        let mut replica =
+            pg_config("postgres://docker@replica/docker?options=-c%20search_path%3Dapp");
+        apply_statement_timeout(&mut primary, Some(&mut replica), Duration::from_secs(2));
+        assert_eq!(primary.get_options(), Some("-c statement_timeout=2000"));
+        assert_eq!(
+            replica.get_options(),
+            Some("-c search_path=app -c statement_timeout=2000")
+        );
+
+        let config = from_env_with(&[("QUERY_TIMEOUT_MS", "0")]).unwrap();
+        assert_eq!(config.database.get_options(), None);
+    }
+
+    #[cfg(feature = "tls")]
+    #[test]
+    fn tls_builds_a_connector_when_required() {
+        assert!(tls_connector(true).is_ok());
+        assert!(tls_connector(false).is_ok());
+    }
+
+    #[cfg(not(feature = "tls"))]
+    #[test]
+    fn builds_without_tls_reject_require() {
+        assert!(matches!(tls_connector(true), Err(Error::TlsDisabled)));
+        assert!(tls_connector(false).is_ok());
+    }
+
+    #[test]
+    fn rejects_cors_entries_that_are_not_origins() {
+        assert!(is_origin("http://localhost:3000"));
+        assert!(is_origin("https://app.example.com"));
+        for origin in [
+            "*",
+            "localhost:3000",
+            "https://app.example.com/",
+            "https://a.com/path",
+        ] {
+            assert!(!is_origin(origin), "{}", origin);
+        }
+
+        let errors = match from_env_with(&[("CORS_ORIGINS", "https://ok.example.com, *")]) {
+            Ok(_) => panic!("`*` was accepted"),
+            Err(errors) => errors,
+        };
+        assert!(matches!(&errors.0[..], [Error::InvalidCorsOrigin(origin)] if origin == "*"));
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        let config = from_env_with(&[]).unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!(config.bind_addr, DEFAULT_BIND_ADDR.parse().unwrap());
+        assert!(config.read_database.is_none());
+    }
+}
@@ -0,0 +1,30 @@
+use std::sync::OnceLock;
+
+static JSON_CASE: OnceLock<JsonCase> = OnceLock::new();
+
+// How multi-word field names are written in JSON (and MessagePack) responses.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum JsonCase {
+    #[default]
+    Snake,
+    Camel,
+}
+
+impl JsonCase {
+    pub fn key(self, snake: &'static str, camel: &'static str) -> &'static str {
+        match self {
+            JsonCase::Snake => snake,
+            JsonCase::Camel => camel,
+        }
+    }
+}
+
+// Set once at startup from `JSON_CASE`. Serialization can't reach the container, so this is the
+// one piece of configuration kept in a global.
+pub fn set_json_case(case: JsonCase) {
+    let _ = JSON_CASE.set(case);
+}
+
+pub fn json_case() -> JsonCase {
+    JSON_CASE.get().copied().unwrap_or_default()
+}
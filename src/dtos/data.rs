@@ -1,17 +1,628 @@
-use crate::models::data::Data;
-use serde::Serialize;
+use crate::dtos::case::{json_case, JsonCase};
+use crate::models::data::{Data, DataStats};
+use async_graphql::SimpleObject;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
+use utoipa::{IntoParams, ToSchema};
+use validator::{Validate, ValidationError};
 
-#[derive(Serialize)]
+// Serialized by hand so multi-word fields follow `JSON_CASE`. Either case is accepted back.
+#[derive(Debug, PartialEq, Deserialize, SimpleObject, ToSchema)]
+#[graphql(name = "Data")]
 pub struct DataDto {
     id: i64,
     name: String,
+    #[serde(alias = "createdAt")]
+    created_at: DateTime<Utc>,
+    #[serde(alias = "updatedAt")]
+    updated_at: DateTime<Utc>,
+    /// Only present on soft-deleted rows
+    #[serde(default, alias = "deletedAt")]
+    deleted_at: Option<DateTime<Utc>>,
+}
+
+impl DataDto {
+    fn serialize_as<S: Serializer>(
+        &self,
+        case: JsonCase,
+        fields: DataFields,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let deleted_at = self.deleted_at.as_ref().filter(|_| fields.deleted_at);
+        let len = [
+            fields.id,
+            fields.name,
+            fields.created_at,
+            fields.updated_at,
+            deleted_at.is_some(),
+        ]
+        .iter()
+        .filter(|&&field| field)
+        .count();
+        let mut state = serializer.serialize_struct("DataDto", len)?;
+        if fields.id {
+            state.serialize_field("id", &self.id)?;
+        }
+        if fields.name {
+            state.serialize_field("name", &self.name)?;
+        }
+        if fields.created_at {
+            state.serialize_field(case.key("created_at", "createdAt"), &self.created_at)?;
+        }
+        if fields.updated_at {
+            state.serialize_field(case.key("updated_at", "updatedAt"), &self.updated_at)?;
+        }
+        if let Some(value) = deleted_at {
+            state.serialize_field(case.key("deleted_at", "deletedAt"), value)?;
+        }
+        state.end()
+    }
+
+    // Leaves out every field not in `fields` when serialized.
+    pub fn only(self, fields: DataFields) -> SparseDataDto {
+        SparseDataDto { dto: self, fields }
+    }
+}
+
+// The `DataDto` fields picked with `?fields=`, all of them unless the client asks for fewer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DataFields {
+    id: bool,
+    name: bool,
+    created_at: bool,
+    updated_at: bool,
+    deleted_at: bool,
+}
+
+impl DataFields {
+    pub const ALL: Self = Self {
+        id: true,
+        name: true,
+        created_at: true,
+        updated_at: true,
+        deleted_at: true,
+    };
+
+    // Parses a comma-separated list of field names, in either case.
+    pub fn parse(fields: Option<&str>) -> Result<Self, String> {
+        let fields = match fields {
+            Some(fields) => fields,
+            None => return Ok(Self::ALL),
+        };
+        let mut picked = Self {
+            id: false,
+            name: false,
+            created_at: false,
+            updated_at: false,
+            deleted_at: false,
+        };
+        for field in fields.split(',').map(str::trim) {
+            match field {
+                "id" => picked.id = true,
+                "name" => picked.name = true,
+                "created_at" | "createdAt" => picked.created_at = true,
+                "updated_at" | "updatedAt" => picked.updated_at = true,
+                "deleted_at" | "deletedAt" => picked.deleted_at = true,
+                "" => return Err("fields must not be empty".to_owned()),
+                _ => {
+                    return Err(format!(
+                        "unknown field `{}`, expected one of id, name, created_at, updated_at, \
+                         deleted_at",
+                        field
+                    ))
+                }
+            }
+        }
+        Ok(picked)
+    }
+}
+
+// The picked fields in `CSV_HEADER` order, so lists naming the same fields display the same.
+impl fmt::Display for DataFields {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fields = [
+            (self.id, "id"),
+            (self.name, "name"),
+            (self.created_at, "created_at"),
+            (self.updated_at, "updated_at"),
+            (self.deleted_at, "deleted_at"),
+        ];
+        let picked = fields
+            .iter()
+            .filter(|(picked, _)| *picked)
+            .map(|(_, name)| *name)
+            .collect::<Vec<_>>();
+        f.write_str(&picked.join(","))
+    }
+}
+
+// A `DataDto` with only some of its fields, from `DataDto::only`.
+pub struct SparseDataDto {
+    dto: DataDto,
+    fields: DataFields,
+}
+
+impl Serialize for SparseDataDto {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.dto.serialize_as(json_case(), self.fields, serializer)
+    }
+}
+
+// Column names for `DataDto::to_csv_record`, ending with the CRLF that RFC 4180 uses.
+pub const CSV_HEADER: &str = "id,name,created_at,updated_at,deleted_at\r\n";
+
+impl DataDto {
+    // One CSV line in `CSV_HEADER` order. Timestamps match the JSON form, and `deleted_at` is empty
+    // for live rows.
+    pub fn to_csv_record(&self) -> String {
+        let timestamp = |t: &DateTime<Utc>| t.to_rfc3339_opts(SecondsFormat::AutoSi, true);
+        format!(
+            "{},{},{},{},{}\r\n",
+            self.id,
+            csv_field(&self.name),
+            timestamp(&self.created_at),
+            timestamp(&self.updated_at),
+            self.deleted_at.as_ref().map(timestamp).unwrap_or_default(),
+        )
+    }
+}
+
+// Quotes fields containing a delimiter, quote or line break, doubling any quotes inside.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+impl Serialize for DataDto {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.serialize_as(json_case(), DataFields::ALL, serializer)
+    }
 }
 
 impl From<Data> for DataDto {
     fn from(data: Data) -> Self {
         Self {
-            id: data.id,
+            id: data.id.into(),
+            name: data.name,
+            created_at: data.created_at,
+            updated_at: data.updated_at,
+            deleted_at: data.deleted_at,
+        }
+    }
+}
+
+// A compact shape for lists, with a label clients can show as-is.
+#[derive(Serialize, ToSchema)]
+pub struct DataSummaryDto {
+    id: i64,
+    name: String,
+    /// Formatted as `#{id}: {name}`
+    display: String,
+}
+
+impl From<Data> for DataSummaryDto {
+    fn from(data: Data) -> Self {
+        Self {
+            display: format!("#{}: {}", data.id, data.name),
+            id: data.id.into(),
             name: data.name,
         }
     }
 }
+
+// A page from `GET /data?after=`, with the cursor for the page after it.
+#[derive(ToSchema)]
+pub struct DataPageDto {
+    pub(crate) data: Vec<DataDto>,
+    /// Pass as `after` to get the next page, `null` on the last page
+    pub(crate) next_after: Option<i64>,
+}
+
+impl Serialize for DataPageDto {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("DataPageDto", 2)?;
+        state.serialize_field("data", &self.data)?;
+        state.serialize_field(json_case().key("next_after", "nextAfter"), &self.next_after)?;
+        state.end()
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CountDto {
+    pub(crate) count: i64,
+}
+
+// Serialized by hand so multi-word fields follow `JSON_CASE`, like `DataPageDto`.
+#[derive(ToSchema)]
+pub struct StatsDto {
+    /// Number of live rows
+    count: i64,
+    /// `null` when there are no live rows
+    min_id: Option<i64>,
+    /// `null` when there are no live rows
+    max_id: Option<i64>,
+    /// Mean name length in characters, `null` when there are no live rows
+    avg_name_length: Option<f64>,
+}
+
+impl From<DataStats> for StatsDto {
+    fn from(stats: DataStats) -> Self {
+        Self {
+            count: stats.count,
+            min_id: stats.min_id,
+            max_id: stats.max_id,
+            avg_name_length: stats.avg_name_length,
+        }
+    }
+}
+
+// Both parts are read from the same snapshot, so `recent` never holds rows `stats` didn't count.
+#[derive(Serialize, ToSchema)]
+pub struct OverviewDto {
+    pub(crate) stats: StatsDto,
+    pub(crate) recent: Vec<DataDto>,
+}
+
+impl Serialize for StatsDto {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let case = json_case();
+        let mut state = serializer.serialize_struct("StatsDto", 4)?;
+        state.serialize_field("count", &self.count)?;
+        state.serialize_field(case.key("min_id", "minId"), &self.min_id)?;
+        state.serialize_field(case.key("max_id", "maxId"), &self.max_id)?;
+        state.serialize_field(
+            case.key("avg_name_length", "avgNameLength"),
+            &self.avg_name_length,
+        )?;
+        state.end()
+    }
+}
+
+#[derive(Deserialize, ToSchema, Validate)]
+pub struct CreateDataDto {
+    #[schema(min_length = 1, max_length = 255)]
+    #[validate(
+        length(min = 1, max = 255, message = "must be between 1 and 255 characters"),
+        custom(function = "not_blank")
+    )]
+    pub(crate) name: String,
+}
+
+#[derive(Deserialize, ToSchema, Validate)]
+pub struct UpdateDataDto {
+    #[schema(min_length = 1, max_length = 255)]
+    #[validate(
+        length(min = 1, max = 255, message = "must be between 1 and 255 characters"),
+        custom(function = "not_blank")
+    )]
+    pub(crate) name: String,
+}
+
+#[derive(Deserialize, ToSchema, Validate)]
+pub struct UpdateManyDto {
+    #[schema(min_items = 1)]
+    #[validate(length(min = 1, message = "must not be empty"))]
+    pub(crate) ids: Vec<i64>,
+    #[schema(min_length = 1, max_length = 255)]
+    #[validate(
+        length(min = 1, max = 255, message = "must be between 1 and 255 characters"),
+        custom(function = "not_blank")
+    )]
+    pub(crate) name: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct UpdatedDto {
+    pub(crate) updated: u64,
+}
+
+fn not_blank(value: &str) -> Result<(), ValidationError> {
+    if value.trim().is_empty() {
+        let mut error = ValidationError::new("blank");
+        error.message = Some("must not be blank".into());
+        return Err(error);
+    }
+    Ok(())
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SearchQueryDto {
+    /// Case-insensitive substring to match against names
+    #[serde(default)]
+    pub(crate) q: String,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ViewQueryDto {
+    /// Shape of each row, one of `full` (default) or `summary`
+    view: Option<String>,
+    /// Wrap the rows in a `PagedDataDto` with the total row count
+    #[serde(default)]
+    pub(crate) paged: bool,
+}
+
+impl ViewQueryDto {
+    pub fn is_summary(&self) -> Result<bool, &'static str> {
+        match self.view.as_deref() {
+            None | Some("full") => Ok(false),
+            Some("summary") => Ok(true),
+            Some(_) => Err("view must be one of full, summary"),
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeletedQueryDto {
+    /// Also return soft-deleted rows
+    #[serde(default)]
+    pub(crate) include_deleted: bool,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct FieldsQueryDto {
+    /// Comma-separated fields to return, e.g. `id,name`. Defaults to every field
+    fields: Option<String>,
+}
+
+impl FieldsQueryDto {
+    pub fn is_set(&self) -> bool {
+        self.fields.is_some()
+    }
+
+    pub fn resolve(&self) -> Result<DataFields, String> {
+        DataFields::parse(self.fields.as_deref())
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RangeQueryDto {
+    /// First id to include
+    pub(crate) from: i64,
+    /// Last id to include, must not be less than `from`
+    pub(crate) to: i64,
+}
+
+impl RangeQueryDto {
+    pub fn check(&self) -> Result<(), &'static str> {
+        if self.from > self.to {
+            return Err("from must not be greater than to");
+        }
+        Ok(())
+    }
+}
+
+pub const DEFAULT_RECENT_LIMIT: i64 = 10;
+pub const MAX_RECENT_LIMIT: i64 = 100;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RecentQueryDto {
+    /// Maximum number of rows to return, defaults to 10 and is capped at 100
+    limit: Option<i64>,
+}
+
+impl RecentQueryDto {
+    pub fn resolve(&self) -> Result<i64, &'static str> {
+        let limit = self.limit.unwrap_or(DEFAULT_RECENT_LIMIT);
+        if limit < 0 {
+            return Err("limit must not be negative");
+        }
+        Ok(limit.min(MAX_RECENT_LIMIT))
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BatchQueryDto {
+    /// Comma-separated list of ids
+    #[serde(default)]
+    ids: String,
+}
+
+impl BatchQueryDto {
+    pub fn parse_ids(&self) -> Result<Vec<i64>, &'static str> {
+        if self.ids.trim().is_empty() {
+            return Err("ids must not be empty");
+        }
+        self.ids
+            .split(',')
+            .map(|id| id.trim().parse())
+            .collect::<Result<_, _>>()
+            .map_err(|_| "ids must be a comma-separated list of integers")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dtos::page::PagedDto;
+    use crate::services::error::Error as ServiceError;
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    fn dto(id: i64, name: &str, deleted_at: Option<DateTime<Utc>>) -> DataDto {
+        DataDto {
+            id,
+            name: name.to_owned(),
+            created_at: Utc.with_ymd_and_hms(2020, 1, 2, 3, 4, 5).unwrap(),
+            updated_at: Utc.with_ymd_and_hms(2020, 1, 2, 3, 4, 6).unwrap(),
+            deleted_at,
+        }
+    }
+
+    fn round_trip(dto: &DataDto) -> DataDto {
+        serde_json::from_str(&serde_json::to_string(dto).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn serializes_to_wire_format() {
+        let value = serde_json::to_value(dto(1, "Paul", None)).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "id": 1,
+                "name": "Paul",
+                "created_at": "2020-01-02T03:04:05Z",
+                "updated_at": "2020-01-02T03:04:06Z",
+            })
+        );
+    }
+
+    #[test]
+    fn serializes_in_camel_case() {
+        let deleted_at = Utc.with_ymd_and_hms(2021, 6, 7, 8, 9, 10).unwrap();
+        let dto = dto(1, "Paul", Some(deleted_at));
+        let value = dto
+            .serialize_as(
+                JsonCase::Camel,
+                DataFields::ALL,
+                serde_json::value::Serializer,
+            )
+            .unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "id": 1,
+                "name": "Paul",
+                "createdAt": "2020-01-02T03:04:05Z",
+                "updatedAt": "2020-01-02T03:04:06Z",
+                "deletedAt": "2021-06-07T08:09:10Z",
+            })
+        );
+        assert_eq!(serde_json::from_value::<DataDto>(value).unwrap(), dto);
+    }
+
+    #[test]
+    fn serializes_only_the_requested_fields() {
+        let fields = DataFields::parse(Some("id, updated_at")).unwrap();
+        let value = serde_json::to_value(dto(1, "Paul", None).only(fields)).unwrap();
+        assert_eq!(
+            value,
+            json!({ "id": 1, "updated_at": "2020-01-02T03:04:06Z" })
+        );
+
+        let deleted_at = Utc.with_ymd_and_hms(2021, 6, 7, 8, 9, 10).unwrap();
+        let fields = DataFields::parse(Some("deletedAt")).unwrap();
+        let value = serde_json::to_value(dto(1, "Paul", Some(deleted_at)).only(fields)).unwrap();
+        assert_eq!(value, json!({ "deleted_at": "2021-06-07T08:09:10Z" }));
+        let value = serde_json::to_value(dto(1, "Paul", None).only(fields)).unwrap();
+        assert_eq!(value, json!({}));
+    }
+
+    #[test]
+    fn rejects_unknown_or_empty_fields() {
+        assert_eq!(DataFields::parse(None), Ok(DataFields::ALL));
+        assert!(DataFields::parse(Some("id,secret"))
+            .unwrap_err()
+            .contains("`secret`"));
+        assert!(DataFields::parse(Some("")).is_err());
+        assert!(DataFields::parse(Some("id,")).is_err());
+    }
+
+    #[test]
+    fn displays_fields_in_a_fixed_order() {
+        let fields = DataFields::parse(Some("updatedAt, id")).unwrap();
+        assert_eq!(fields.to_string(), "id,updated_at");
+        assert_eq!(
+            DataFields::ALL.to_string(),
+            "id,name,created_at,updated_at,deleted_at"
+        );
+    }
+
+    #[test]
+    fn pages_report_whether_rows_remain() {
+        let page = PagedDto::new(vec![dto(3, "Santiago", None)], 4, 1, 2);
+        let value = serde_json::to_value(&page).unwrap();
+        assert_eq!(value["items"][0]["id"], 3);
+        assert_eq!(value["total"], 4);
+        assert_eq!(value["has_more"], true);
+
+        let last = PagedDto::new(vec![dto(4, "Oliver", None)], 4, 1, 3);
+        assert_eq!(serde_json::to_value(&last).unwrap()["has_more"], false);
+    }
+
+    #[test]
+    fn includes_deleted_at_only_when_set() {
+        let deleted_at = Utc.with_ymd_and_hms(2021, 6, 7, 8, 9, 10).unwrap();
+        let value = serde_json::to_value(dto(1, "Paul", Some(deleted_at))).unwrap();
+        assert_eq!(value["deleted_at"], "2021-06-07T08:09:10Z");
+
+        let dto = dto(1, "Paul", Some(deleted_at));
+        assert_eq!(round_trip(&dto), dto);
+    }
+
+    #[test]
+    fn round_trips_unicode_names() {
+        let dto = dto(2, "Zoë 🦀 名前 \"quoted\"", None);
+        assert_eq!(round_trip(&dto), dto);
+    }
+
+    #[test]
+    fn round_trips_ids_near_i64_max() {
+        let dto = dto(i64::MAX, "Max", None);
+        let json = serde_json::to_string(&dto).unwrap();
+        assert!(json.contains("\"id\":9223372036854775807"));
+        assert_eq!(round_trip(&dto), dto);
+    }
+
+    #[test]
+    fn deserializes_without_deleted_at() {
+        let dto: DataDto = serde_json::from_value(json!({
+            "id": 3,
+            "name": "Santiago",
+            "created_at": "2020-01-02T03:04:05Z",
+            "updated_at": "2020-01-02T03:04:06Z",
+        }))
+        .unwrap();
+        assert_eq!(dto.deleted_at, None);
+        assert_eq!(dto.name, "Santiago");
+    }
+
+    #[test]
+    fn writes_csv_records() {
+        assert_eq!(
+            dto(1, "Paul", None).to_csv_record(),
+            "1,Paul,2020-01-02T03:04:05Z,2020-01-02T03:04:06Z,\r\n"
+        );
+        let deleted_at = Utc.with_ymd_and_hms(2020, 1, 2, 3, 4, 7).unwrap();
+        assert_eq!(
+            dto(2, "Paul", Some(deleted_at)).to_csv_record(),
+            "2,Paul,2020-01-02T03:04:05Z,2020-01-02T03:04:06Z,2020-01-02T03:04:07Z\r\n"
+        );
+    }
+
+    #[test]
+    fn escapes_csv_names() {
+        let record = |name| dto(1, name, None).to_csv_record();
+        assert!(record("Smith, Paul").starts_with("1,\"Smith, Paul\","));
+        assert!(record("Paul \"P\" Smith").starts_with("1,\"Paul \"\"P\"\" Smith\","));
+        assert!(record("Paul\nSmith").starts_with("1,\"Paul\nSmith\","));
+    }
+
+    #[test]
+    fn reports_validation_messages_by_field() {
+        let dto = UpdateManyDto {
+            ids: vec![],
+            name: " ".to_owned(),
+        };
+        let error = ServiceError::from(dto.validate().unwrap_err());
+        let body = serde_json::to_value(error.to_dto()).unwrap();
+        assert_eq!(
+            body["errors"],
+            json!({
+                "ids": ["must not be empty"],
+                "name": ["must not be blank"],
+            })
+        );
+    }
+}
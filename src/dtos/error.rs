@@ -0,0 +1,15 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use utoipa::ToSchema;
+
+#[derive(Serialize, ToSchema)]
+pub struct ErrorDto {
+    pub(crate) error: String,
+    pub(crate) code: &'static str,
+    /// Messages for each invalid field, keyed by its path (e.g. `name` or `[2].name`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) errors: Option<BTreeMap<String, Vec<String>>>,
+    /// The `X-Request-Id` of the failed request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) request_id: Option<String>,
+}
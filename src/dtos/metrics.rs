@@ -0,0 +1,29 @@
+use crate::postgres::PoolState;
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct PoolStateDto {
+    max_open: u64,
+    connections: u64,
+    in_use: u64,
+    idle: u64,
+    wait_count: u64,
+    wait_duration_ms: u128,
+    max_idle_closed: u64,
+    max_lifetime_closed: u64,
+}
+
+impl From<PoolState> for PoolStateDto {
+    fn from(state: PoolState) -> Self {
+        Self {
+            max_open: state.max_open,
+            connections: state.connections,
+            in_use: state.in_use,
+            idle: state.idle,
+            wait_count: state.wait_count,
+            wait_duration_ms: state.wait_duration.as_millis(),
+            max_idle_closed: state.max_idle_closed,
+            max_lifetime_closed: state.max_lifetime_closed,
+        }
+    }
+}
@@ -1 +1,8 @@
+pub mod case;
 pub mod data;
+pub mod error;
+pub mod health;
+pub mod metrics;
+pub mod operations;
+pub mod page;
+pub mod sort;
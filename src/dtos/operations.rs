@@ -0,0 +1,108 @@
+use serde::Serialize;
+use utoipa::openapi::path::{ParameterIn, PathItemType};
+use utoipa::openapi::{OpenApi, Required};
+use utoipa::ToSchema;
+
+// The body of `OPTIONS /api/v1/data`, summarizing the OpenAPI spec.
+#[derive(Serialize, ToSchema)]
+pub struct OperationsDto {
+    operations: Vec<OperationDto>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct OperationDto {
+    /// e.g. `GET`
+    method: &'static str,
+    path: String,
+    /// What a successful response contains
+    description: Option<String>,
+    parameters: Vec<ParameterDto>,
+    /// Whether the operation takes a request body
+    body: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ParameterDto {
+    name: String,
+    /// `path`, `query` or `header`
+    #[serde(rename = "in")]
+    location: &'static str,
+    required: bool,
+    description: Option<String>,
+}
+
+impl OperationsDto {
+    // Methods served for `path`, in the order they are listed.
+    pub fn methods<'a>(&'a self, path: &'a str) -> impl Iterator<Item = &'static str> + 'a {
+        self.operations
+            .iter()
+            .filter(move |operation| operation.path == path)
+            .map(|operation| operation.method)
+    }
+}
+
+impl From<&OpenApi> for OperationsDto {
+    fn from(spec: &OpenApi) -> Self {
+        let operations = spec
+            .paths
+            .paths
+            .iter()
+            .flat_map(|(path, item)| {
+                item.operations.iter().map(move |(method, operation)| {
+                    let description = operation
+                        .responses
+                        .responses
+                        .iter()
+                        .find(|(status, _)| status.starts_with('2'))
+                        .and_then(|(_, response)| match response {
+                            utoipa::openapi::RefOr::T(response) => {
+                                Some(response.description.clone())
+                            }
+                            utoipa::openapi::RefOr::Ref(_) => None,
+                        });
+                    OperationDto {
+                        method: method_name(method),
+                        path: path.clone(),
+                        description,
+                        parameters: operation
+                            .parameters
+                            .iter()
+                            .flatten()
+                            .map(|parameter| ParameterDto {
+                                name: parameter.name.clone(),
+                                location: location_name(&parameter.parameter_in),
+                                required: matches!(parameter.required, Required::True),
+                                description: parameter.description.clone(),
+                            })
+                            .collect(),
+                        body: operation.request_body.is_some(),
+                    }
+                })
+            })
+            .collect();
+        Self { operations }
+    }
+}
+
+fn method_name(method: &PathItemType) -> &'static str {
+    match method {
+        PathItemType::Get => "GET",
+        PathItemType::Post => "POST",
+        PathItemType::Put => "PUT",
+        PathItemType::Delete => "DELETE",
+        PathItemType::Options => "OPTIONS",
+        PathItemType::Head => "HEAD",
+        PathItemType::Patch => "PATCH",
+        PathItemType::Trace => "TRACE",
+        PathItemType::Connect => "CONNECT",
+    }
+}
+
+fn location_name(location: &ParameterIn) -> &'static str {
+    match location {
+        ParameterIn::Query => "query",
+        ParameterIn::Path => "path",
+        ParameterIn::Header => "header",
+        ParameterIn::Cookie => "cookie",
+    }
+}
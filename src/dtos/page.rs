@@ -0,0 +1,84 @@
+use crate::dtos::case::json_case;
+use crate::dtos::data::DataDto;
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
+use utoipa::{IntoParams, ToSchema};
+
+pub const DEFAULT_LIMIT: i64 = 50;
+pub const MAX_LIMIT: i64 = 200;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PageQueryDto {
+    /// Maximum number of rows to return, defaults to 50 and is capped at 200
+    limit: Option<i64>,
+    /// Number of rows to skip, defaults to 0
+    offset: Option<i64>,
+}
+
+impl PageQueryDto {
+    pub fn new(limit: Option<i64>, offset: Option<i64>) -> Self {
+        Self { limit, offset }
+    }
+
+    pub fn has_offset(&self) -> bool {
+        self.offset.is_some()
+    }
+
+    pub fn resolve(&self) -> Result<(i64, i64), &'static str> {
+        let limit = self.limit.unwrap_or(DEFAULT_LIMIT);
+        let offset = self.offset.unwrap_or(0);
+        if limit < 0 {
+            return Err("limit must not be negative");
+        }
+        if offset < 0 {
+            return Err("offset must not be negative");
+        }
+        Ok((limit.min(MAX_LIMIT), offset))
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CursorQueryDto {
+    /// Only return rows with a greater id, in id order. Pass the previous page's `next_after`
+    pub(crate) after: Option<i64>,
+}
+
+// A page from `GET /data?paged=true`. Serialized by hand so `has_more` follows `JSON_CASE`.
+#[derive(ToSchema)]
+#[aliases(PagedDataDto = PagedDto<DataDto>)]
+pub struct PagedDto<T> {
+    items: Vec<T>,
+    /// Number of rows across every page
+    total: i64,
+    limit: i64,
+    offset: i64,
+    /// Whether rows remain after this page
+    has_more: bool,
+}
+
+impl<T> PagedDto<T> {
+    pub fn new(items: Vec<T>, total: i64, limit: i64, offset: i64) -> Self {
+        let has_more = offset.saturating_add(items.len() as i64) < total;
+        Self {
+            items,
+            total,
+            limit,
+            offset,
+            has_more,
+        }
+    }
+}
+
+impl<T: Serialize> Serialize for PagedDto<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("PagedDto", 5)?;
+        state.serialize_field("items", &self.items)?;
+        state.serialize_field("total", &self.total)?;
+        state.serialize_field("limit", &self.limit)?;
+        state.serialize_field("offset", &self.offset)?;
+        state.serialize_field(json_case().key("has_more", "hasMore"), &self.has_more)?;
+        state.end()
+    }
+}
@@ -0,0 +1,32 @@
+use crate::models::sort::{Sort, SortField, SortOrder};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SortQueryDto {
+    /// Field to sort by, one of `id` (default) or `name`
+    sort: Option<String>,
+    /// Sort direction, one of `asc` (default) or `desc`
+    order: Option<String>,
+}
+
+impl SortQueryDto {
+    pub fn is_set(&self) -> bool {
+        self.sort.is_some() || self.order.is_some()
+    }
+
+    pub fn resolve(&self) -> Result<Sort, &'static str> {
+        let field = match self.sort.as_deref() {
+            None | Some("id") => SortField::Id,
+            Some("name") => SortField::Name,
+            Some(_) => return Err("sort must be one of id, name"),
+        };
+        let order = match self.order.as_deref() {
+            None | Some("asc") => SortOrder::Asc,
+            Some("desc") => SortOrder::Desc,
+            Some(_) => return Err("order must be one of asc, desc"),
+        };
+        Ok(Sort { field, order })
+    }
+}
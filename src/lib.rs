@@ -0,0 +1,12 @@
+pub mod config;
+pub mod dtos;
+pub mod listener;
+pub mod logging;
+pub mod metrics;
+pub mod middleware;
+pub mod models;
+pub mod postgres;
+pub mod repositories;
+pub mod routes;
+pub mod services;
+pub mod wiring;
@@ -0,0 +1,120 @@
+use crate::models::data::{Data, DataId};
+use crate::postgres::AppTls;
+use crate::repositories::repo::DbData;
+use crate::services::caching::DataCache;
+use crate::services::events::DataEvents;
+use futures::channel::mpsc;
+use futures::stream::{self, StreamExt};
+use mobc_postgres::tokio_postgres::{
+    AsyncMessage, Client, Config as PgConfig, Error as PostgresError,
+};
+use std::convert::TryFrom;
+use std::sync::Arc;
+use std::time::Duration;
+
+const CHANGED_CHANNEL: &str = "data_changed";
+const INSERTED_CHANNEL: &str = "data_inserted";
+const RECONNECT_BACKOFF_START: Duration = Duration::from_millis(100);
+const RECONNECT_BACKOFF_MAX: Duration = Duration::from_secs(30);
+
+// Keeps a dedicated connection listening on `data_changed` and `data_inserted`. Changed rows are
+// dropped from the cache, so writes made through other servers (or by hand) don't leave stale
+// entries behind, and inserted rows are published to `GET /api/v1/data/events` subscribers. The
+// notifications are sent by triggers on the `data` table, see migrations/0007 and 0008. Runs until
+// the server shuts down, reconnecting with exponential backoff whenever the connection is lost.
+pub async fn listen_for_changes(
+    config: PgConfig,
+    tls: AppTls,
+    cache: Arc<DataCache>,
+    events: Arc<DataEvents>,
+) {
+    let mut delay = RECONNECT_BACKOFF_START;
+    loop {
+        // Spelled out because `AppTls` is `Copy` without the `tls` feature and clippy would object.
+        match listen(&config, Clone::clone(&tls), &cache, &events, &mut delay).await {
+            Ok(()) => tracing::warn!(
+                ?delay,
+                "Change notification connection closed, reconnecting"
+            ),
+            Err(e) => tracing::warn!(
+                ?delay,
+                "Change notification connection failed ({}), reconnecting",
+                e
+            ),
+        }
+        // Anything that changed while disconnected was missed.
+        cache.clear();
+        actix_rt::time::sleep(delay).await;
+        delay = (delay * 2).min(RECONNECT_BACKOFF_MAX);
+    }
+}
+
+async fn listen(
+    config: &PgConfig,
+    tls: AppTls,
+    cache: &DataCache,
+    events: &DataEvents,
+    delay: &mut Duration,
+) -> Result<(), PostgresError> {
+    let (client, mut connection) = config.connect(tls).await?;
+    // Notifications only arrive while the connection is polled, which `Client` leaves to its
+    // caller, so that happens on its own task. They are handed back here, where `client` can look
+    // up inserted rows.
+    let (sender, mut notifications) = mpsc::unbounded();
+    let connection = actix_rt::spawn(async move {
+        let mut messages = stream::poll_fn(move |cx| connection.poll_message(cx));
+        while let Some(message) = messages.next().await {
+            if let AsyncMessage::Notification(notification) = message? {
+                let _ = sender.unbounded_send(notification);
+            }
+        }
+        Ok(())
+    });
+    client
+        .batch_execute(&format!(
+            "LISTEN {}; LISTEN {}",
+            CHANGED_CHANNEL, INSERTED_CHANNEL
+        ))
+        .await?;
+    tracing::info!(
+        channels = ?[CHANGED_CHANNEL, INSERTED_CHANNEL],
+        "Listening for data changes"
+    );
+    *delay = RECONNECT_BACKOFF_START;
+    // Ends once the connection does, since that drops `sender`.
+    while let Some(notification) = notifications.next().await {
+        let id = notification.payload().parse::<DataId>();
+        match (notification.channel(), id) {
+            (INSERTED_CHANNEL, Ok(id)) => publish_inserted(&client, events, id).await?,
+            (INSERTED_CHANNEL, Err(_)) => {}
+            (_, Ok(id)) => cache.invalidate(id),
+            (_, Err(_)) => cache.clear(),
+        }
+    }
+    connection.await.unwrap_or(Ok(()))
+}
+
+// Notifications only carry the id, since a whole row may not fit in a payload.
+async fn publish_inserted(
+    client: &Client,
+    events: &DataEvents,
+    id: DataId,
+) -> Result<(), PostgresError> {
+    if !events.has_subscribers() {
+        return Ok(());
+    }
+    let row = client
+        .query_opt(
+            "SELECT id, name, created_at, updated_at, deleted_at FROM data WHERE id=$1::BIGINT",
+            &[&i64::from(id)],
+        )
+        .await?;
+    // A row deleted again before it could be read is skipped.
+    if let Some(row) = row {
+        match Data::try_from(DbData::from(&row)) {
+            Ok(data) => events.publish(&data),
+            Err(e) => tracing::warn!("Not publishing inserted row: {}", e),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,12 @@
+use crate::config::{Config, LogFormat};
+use tracing_subscriber::EnvFilter;
+
+// Installs the global subscriber from `RUST_LOG`/`LOG_LEVEL` and `LOG_FORMAT`. Must be called
+// once, before anything is logged.
+pub fn init(config: &Config) {
+    let builder = tracing_subscriber::fmt().with_env_filter(EnvFilter::new(&config.log_filter));
+    match config.log_format {
+        LogFormat::Pretty => builder.init(),
+        LogFormat::Json => builder.json().init(),
+    }
+}
@@ -1,36 +1,118 @@
-use crate::{
-    postgres::PostgresPoolProvider, repositories::repo::RepositoryProvider,
+use actix_web::middleware::{Compress, Condition};
+use actix_web::{App, HttpServer};
+use coi::container;
+#[cfg(not(feature = "in-memory"))]
+use coi_actix_sample::listener::listen_for_changes;
+use coi_actix_sample::repositories::memory::InMemoryRepositoryProvider;
+#[cfg(not(feature = "in-memory"))]
+use coi_actix_sample::repositories::snapshot::SnapshotRepositoryProvider;
+#[cfg(feature = "in-memory")]
+use coi_actix_sample::repositories::snapshot::UnsharedSnapshotRepositoryProvider;
+#[cfg(not(feature = "in-memory"))]
+use coi_actix_sample::services::caching::DataCache;
+#[cfg(not(feature = "in-memory"))]
+use coi_actix_sample::services::events::DataEvents;
+use coi_actix_sample::{
+    config::{Config, ConfigProvider},
+    dtos::case::set_json_case,
+    logging,
+    metrics::{MetricsRegistry, MetricsRegistryProvider},
+    middleware::auth::WriteTokenProvider,
+    middleware::body_log::BodyLog,
+    middleware::cors::cors,
+    middleware::metrics::RequestMetrics,
+    middleware::rate_limit::{RateLimit, RateLimiter},
+    middleware::request_id::RequestIdHeader,
+    middleware::response_time::ResponseTime,
+    middleware::timeout::Timeout,
+    postgres::AppTls,
+    repositories::slow_query::SlowQueryLogProvider,
+    repositories::snapshot::RequestConnectionProvider,
+    routes,
+    services::admin::{AdminServiceProvider, AdminTokenProvider},
+    services::aggregate::AggregateServiceProvider,
+    services::caching::{CachingServiceProvider, DataCacheProvider},
+    services::events::DataEventsProvider,
+    services::health::{HealthServiceProvider, Readiness, ReadinessProvider},
+    services::idempotency::IdempotencyStoreProvider,
+    services::metrics::MetricsServiceProvider,
     services::service::ServiceProvider,
+    services::snapshot::SnapshotServiceProvider,
+    wiring::{build_pools, drain_pools, repository_provider},
 };
-use actix_web::{middleware, App, HttpServer};
-use coi::container;
-use mobc_postgres::{mobc::Pool, tokio_postgres::NoTls, PgConnectionManager};
-
-mod dtos;
-mod models;
-mod postgres;
-mod repositories;
-mod routes;
-mod services;
+use std::sync::Arc;
+use tracing_actix_web::TracingLogger;
 
 #[actix_rt::main]
 async fn main() -> Result<(), String> {
-    std::env::set_var("RUST_LOG", "actix_server=debug,actix_web=debug");
-    env_logger::init();
+    // Printed directly, since returning it would escape the newlines between the problems.
+    let config = match Config::from_env() {
+        Ok(config) => Arc::new(config),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    logging::init(&config);
+    set_json_case(config.json_case);
+    serve(config).await
+}
 
-    let config = "host=127.0.0.1 dbname=docker port=45432 user=docker password=docker"
-        .parse()
-        .map_err(|e| format!("{}", e))?;
-    let manager = PgConnectionManager::new(config, NoTls);
-    let pool = Pool::builder().max_open(20).build(manager);
-    let pool_provider = PostgresPoolProvider::new(pool);
+async fn serve(config: Arc<Config>) -> Result<(), String> {
+    let pools = build_pools(&config);
+    #[cfg(not(feature = "in-memory"))]
+    let snapshot_repository_provider = SnapshotRepositoryProvider::<AppTls>::new();
+    #[cfg(feature = "in-memory")]
+    let snapshot_repository_provider = UnsharedSnapshotRepositoryProvider;
+    let metrics_provider = MetricsServiceProvider::<AppTls>::new();
 
     let container = container! {
-        pool => pool_provider; singleton,
-        service => ServiceProvider; scoped,
-        service2 => ServiceProvider; scoped,
-        repository => RepositoryProvider; scoped,
+        config => ConfigProvider::new(config.clone()); singleton,
+        pool => pools.pool; singleton,
+        read_pool => pools.read_pool; singleton,
+        service => CachingServiceProvider; scoped,
+        service2 => CachingServiceProvider; scoped,
+        uncached_service => ServiceProvider; scoped,
+        cache => DataCacheProvider; singleton,
+        events => DataEventsProvider; singleton,
+        idempotency => IdempotencyStoreProvider; singleton,
+        repository => repository_provider(&config); scoped,
+        memory_repository => InMemoryRepositoryProvider::new(); scoped,
+        slow_queries => SlowQueryLogProvider; singleton,
+        request_connection => RequestConnectionProvider::<AppTls>::new(); scoped,
+        snapshot_repository => snapshot_repository_provider; scoped,
+        snapshot => SnapshotServiceProvider; scoped,
+        health => HealthServiceProvider; scoped,
+        readiness => ReadinessProvider; singleton,
+        admin => AdminServiceProvider; scoped,
+        aggregate => AggregateServiceProvider; scoped,
+        admin_token => AdminTokenProvider; singleton,
+        write_token => WriteTokenProvider; singleton,
+        metrics => metrics_provider; scoped,
+        registry => MetricsRegistryProvider; singleton,
     };
+    let registry = container
+        .resolve::<MetricsRegistry>("registry")
+        .map_err(|e| format!("{}", e))?;
+    let readiness = container
+        .resolve::<Readiness>("readiness")
+        .map_err(|e| format!("{}", e))?;
+    // Notifications aren't replicated, so this listens on the primary even with a read replica.
+    #[cfg(not(feature = "in-memory"))]
+    {
+        let cache = container
+            .resolve::<DataCache>("cache")
+            .map_err(|e| format!("{}", e))?;
+        let events = container
+            .resolve::<DataEvents>("events")
+            .map_err(|e| format!("{}", e))?;
+        actix_rt::spawn(listen_for_changes(
+            config.database.clone(),
+            Clone::clone(&config.tls),
+            cache,
+            events,
+        ));
+    }
 
     #[cfg(feature = "debug")]
     {
@@ -45,20 +127,79 @@ async fn main() -> Result<(), String> {
         use std::fs::File;
         use std::io::Write;
         let mut file = File::create("deps.dot").expect("Cannot create dot file");
-        file.write(container.dot_graph().as_bytes())
+        file.write_all(container.dot_graph().as_bytes())
             .expect("Cannot write graph to dot file");
     }
 
-    HttpServer::new(move || {
+    let shutdown_timeout = config.shutdown_timeout;
+    let bind_addr = config.bind_addr;
+    let workers = config.workers;
+    let rate_limit = config.rate_limit;
+    // One limiter shared by every worker, so the limit is per process rather than per thread.
+    let limiter = Arc::new(RateLimiter::new(rate_limit.unwrap_or(0)));
+    let cors_origins = config.cors_origins.clone();
+    let request_timeout = config.request_timeout;
+    let compression = config.compression;
+    let body_log = config.body_log.clone();
+    let log_bodies = body_log.is_some();
+    let body_log = Arc::new(body_log.unwrap_or_default());
+    let app_container = container.clone();
+    let server = HttpServer::new(move || {
         App::new()
-            .app_data(container.clone())
-            .wrap(middleware::Compress::default())
-            .wrap(middleware::Logger::default())
+            .app_data(app_container.clone())
+            .app_data(routes::content::json_config())
+            // Innermost, so the time covers the handler and little else.
+            .wrap(ResponseTime)
+            // Inside compression, so it sees response bodies before they are compressed.
+            .wrap(Condition::new(log_bodies, BodyLog::new(body_log.clone())))
+            .wrap(Condition::new(
+                request_timeout.is_some(),
+                Timeout::new(request_timeout.unwrap_or_default()),
+            ))
+            .wrap(Condition::new(
+                rate_limit.is_some(),
+                RateLimit::new(limiter.clone()),
+            ))
+            .wrap(Condition::new(compression, Compress::default()))
+            .wrap(RequestMetrics::new(registry.clone()))
+            // Outside the rate limiter so preflight requests are answered without using a token.
+            .wrap(Condition::new(
+                !cors_origins.is_empty(),
+                cors(&cors_origins),
+            ))
+            .wrap(RequestIdHeader)
+            .wrap(TracingLogger::default())
+            .configure(routes::admin::route_config)
             .configure(routes::data::route_config)
+            .configure(routes::docs::route_config)
+            .configure(routes::graphql::route_config)
+            .configure(routes::health::route_config)
+            .configure(routes::metrics::route_config)
     })
-    .bind("127.0.0.1:8000")
-    .map_err(|e| format!("{}", e))?
-    .run()
-    .await
-    .map_err(|e| format!("{}", e))
+    .shutdown_timeout(shutdown_timeout)
+    .workers(workers)
+    .bind(bind_addr)
+    .map_err(|e| format!("{}", e))?;
+    tracing::info!(%bind_addr, workers, "Server listening");
+    let server = server.run();
+    let handle = server.handle();
+    let server = actix_rt::spawn(server);
+
+    // Warming up after binding lets liveness probes succeed while `/health/ready` reports that
+    // the server is still starting.
+    if let Err(e) = pools.startup.run(&config).await {
+        handle.stop(false).await;
+        return Err(e);
+    }
+    readiness.mark_ready();
+    server
+        .await
+        .map_err(|e| format!("{}", e))?
+        .map_err(|e| format!("{}", e))?;
+
+    // The server has stopped accepting connections and in-flight requests have finished (or
+    // timed out), so the pool can be drained before the container holding it is dropped.
+    drain_pools(&container).await?;
+    drop(container);
+    Ok(())
 }
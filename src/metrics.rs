@@ -0,0 +1,126 @@
+use crate::postgres::PoolState;
+use actix_web::http::StatusCode;
+use coi::Inject;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[derive(Inject)]
+#[coi(provides pub MetricsRegistry with MetricsRegistry::new())]
+pub struct MetricsRegistry {
+    registry: Registry,
+    request_duration: HistogramVec,
+    responses: IntCounterVec,
+    pool_acquire_duration: HistogramVec,
+    pool_max_open: IntGauge,
+    pool_connections: IntGauge,
+    pool_in_use: IntGauge,
+    pool_idle: IntGauge,
+    pool_waits: IntCounter,
+    // Held while `pool_waits` catches up with the pool's own count, so concurrent scrapes don't
+    // both add the same waits.
+    pool_waits_update: Mutex<()>,
+}
+
+impl MetricsRegistry {
+    fn new() -> Self {
+        let registry = Registry::new();
+        let request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "Time spent handling a request",
+            ),
+            &["method", "route"],
+        )
+        .expect("valid histogram");
+        let responses = IntCounterVec::new(
+            Opts::new("http_responses_total", "Responses sent, by status code"),
+            &["method", "route", "status"],
+        )
+        .expect("valid counter");
+        let pool_acquire_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "pool_acquire_duration_seconds",
+                "Time spent waiting for a pooled connection, including retries",
+            ),
+            &["pool"],
+        )
+        .expect("valid histogram");
+        let gauge = |name, help| IntGauge::new(name, help).expect("valid gauge");
+        let metrics = Self {
+            request_duration,
+            responses,
+            pool_acquire_duration,
+            pool_max_open: gauge("pool_max_open", "Maximum number of open connections"),
+            pool_connections: gauge("pool_connections", "Established connections"),
+            pool_in_use: gauge("pool_in_use", "Connections currently in use"),
+            pool_idle: gauge("pool_idle", "Idle connections"),
+            pool_waits: IntCounter::new("pool_waits_total", "Total connections waited for")
+                .expect("valid counter"),
+            pool_waits_update: Mutex::new(()),
+            registry,
+        };
+        metrics.register();
+        metrics
+    }
+
+    fn register(&self) {
+        let collectors: [Box<dyn prometheus::core::Collector>; 8] = [
+            Box::new(self.request_duration.clone()),
+            Box::new(self.responses.clone()),
+            Box::new(self.pool_acquire_duration.clone()),
+            Box::new(self.pool_max_open.clone()),
+            Box::new(self.pool_connections.clone()),
+            Box::new(self.pool_in_use.clone()),
+            Box::new(self.pool_idle.clone()),
+            Box::new(self.pool_waits.clone()),
+        ];
+        for collector in collectors {
+            self.registry
+                .register(collector)
+                .expect("metrics are only registered once");
+        }
+    }
+
+    pub fn observe_request(
+        &self,
+        method: &str,
+        route: &str,
+        status: StatusCode,
+        elapsed: Duration,
+    ) {
+        self.request_duration
+            .with_label_values(&[method, route])
+            .observe(elapsed.as_secs_f64());
+        self.responses
+            .with_label_values(&[method, route, status.as_str()])
+            .inc();
+    }
+
+    pub fn observe_pool_acquire(&self, pool: &str, elapsed: Duration) {
+        self.pool_acquire_duration
+            .with_label_values(&[pool])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn set_pool_state(&self, state: &PoolState) {
+        self.pool_max_open.set(state.max_open as i64);
+        self.pool_connections.set(state.connections as i64);
+        self.pool_in_use.set(state.in_use as i64);
+        self.pool_idle.set(state.idle as i64);
+        // The pool only reports a running total, so the counter is advanced by what it has missed.
+        let _guard = self.pool_waits_update.lock().unwrap();
+        let missed = state.wait_count.saturating_sub(self.pool_waits.get());
+        self.pool_waits.inc_by(missed);
+    }
+
+    pub fn encode(&self) -> Result<(String, Vec<u8>), prometheus::Error> {
+        let encoder = TextEncoder::new();
+        let mut buffer = vec![];
+        encoder.encode(&self.registry.gather(), &mut buffer)?;
+        Ok((encoder.format_type().to_owned(), buffer))
+    }
+}
@@ -0,0 +1,219 @@
+use crate::config::Config;
+use crate::services::error::Error as ServiceError;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{self, HeaderMap};
+use actix_web::Error;
+use coi::{Container, Inject, Provide};
+use futures::future::{err, ok, Either, Ready};
+use std::sync::Arc;
+
+// The token in an `Authorization: Bearer <token>` header, if there is one.
+pub(crate) fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+// Compares in constant time so response timing doesn't reveal how much of the token matched.
+pub(crate) fn tokens_match(token: &str, candidate: &str) -> bool {
+    token.len() == candidate.len()
+        && token
+            .bytes()
+            .zip(candidate.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+// The bearer token that requests changing data must carry. Without one, they are let through.
+#[derive(Inject)]
+pub struct WriteToken(Option<String>);
+
+// The token is `WRITE_TOKEN`.
+pub struct WriteTokenProvider;
+
+impl Provide for WriteTokenProvider {
+    type Output = WriteToken;
+
+    fn provide(&self, container: &Container) -> coi::Result<Arc<WriteToken>> {
+        let config = container.resolve::<Config>("config")?;
+        Ok(Arc::new(WriteToken(config.write_token.clone())))
+    }
+
+    #[cfg(feature = "debug")]
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["config"]
+    }
+}
+
+// Rejects requests that may change data (anything but `GET`, `HEAD`, `OPTIONS` and `TRACE`) with a
+// `401` unless they carry the container's `write_token`. Reads stay public.
+pub struct RequireWriteToken;
+
+impl<S, B> Transform<S, ServiceRequest> for RequireWriteToken
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequireWriteTokenMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(RequireWriteTokenMiddleware { service })
+    }
+}
+
+pub struct RequireWriteTokenMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for RequireWriteTokenMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Either<S::Future, Ready<Result<Self::Response, Self::Error>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        if req.method().is_safe() || authorized(&req) {
+            Either::Left(self.service.call(req))
+        } else {
+            Either::Right(err(ServiceError::Unauthorized.into()))
+        }
+    }
+}
+
+fn authorized(req: &ServiceRequest) -> bool {
+    let token = match req.app_data::<Container>() {
+        Some(container) => container.resolve::<WriteToken>("write_token"),
+        None => return false,
+    };
+    match token.as_deref() {
+        Ok(WriteToken(None)) => true,
+        Ok(WriteToken(Some(token))) => {
+            bearer_token(req.headers()).is_some_and(|candidate| tokens_match(token, candidate))
+        }
+        Err(e) => {
+            tracing::error!("Failed to resolve write_token: {}", e);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::header::HeaderValue;
+    use actix_web::http::{Method, StatusCode};
+    use actix_web::test::{self, TestRequest};
+    use actix_web::{web, App, HttpResponse};
+    use coi::container;
+
+    struct FixedWriteToken(Option<&'static str>);
+
+    impl Provide for FixedWriteToken {
+        type Output = WriteToken;
+
+        fn provide(&self, _: &Container) -> coi::Result<Arc<WriteToken>> {
+            Ok(Arc::new(WriteToken(self.0.map(str::to_owned))))
+        }
+
+        #[cfg(feature = "debug")]
+        fn dependencies(&self) -> &'static [&'static str] {
+            &[]
+        }
+    }
+
+    // The status and `WWW-Authenticate` header a request to `/data` gets.
+    async fn call(
+        write_token: Option<&'static str>,
+        req: TestRequest,
+    ) -> (StatusCode, Option<HeaderValue>) {
+        let container = container! {
+            write_token => FixedWriteToken(write_token); singleton,
+        };
+        let app = test::init_service(
+            App::new().app_data(container).service(
+                web::scope("/data")
+                    .wrap(RequireWriteToken)
+                    .default_service(web::to(HttpResponse::Ok)),
+            ),
+        )
+        .await;
+        let response = match test::try_call_service(&app, req.uri("/data").to_request()).await {
+            Ok(response) => response.into_parts().1,
+            Err(e) => e.as_response_error().error_response(),
+        };
+        let challenge = response.headers().get(header::WWW_AUTHENTICATE).cloned();
+        (response.status(), challenge)
+    }
+
+    fn post_with(authorization: &str) -> TestRequest {
+        TestRequest::post().insert_header((header::AUTHORIZATION, authorization))
+    }
+
+    #[actix_rt::test]
+    async fn safe_methods_need_no_token() {
+        for method in [Method::GET, Method::HEAD, Method::OPTIONS, Method::TRACE] {
+            let (status, _) = call(Some("secret"), TestRequest::default().method(method)).await;
+            assert_eq!(status, StatusCode::OK);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn rejects_writes_without_the_right_token() {
+        let requests = [
+            TestRequest::post(),
+            TestRequest::delete(),
+            post_with("secret"),
+            post_with("Basic secret"),
+            post_with("Bearer "),
+            post_with("Bearer wrong!"),
+            post_with("Bearer secret2"),
+        ];
+        for req in requests {
+            let (status, challenge) = call(Some("secret"), req).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+            assert_eq!(challenge.unwrap(), "Bearer");
+        }
+    }
+
+    #[actix_rt::test]
+    async fn accepts_writes_with_the_right_token() {
+        for req in [
+            TestRequest::post(),
+            TestRequest::put(),
+            TestRequest::delete(),
+        ] {
+            let req = req.insert_header((header::AUTHORIZATION, "Bearer secret"));
+            let (status, challenge) = call(Some("secret"), req).await;
+            assert_eq!(status, StatusCode::OK);
+            assert!(challenge.is_none());
+        }
+    }
+
+    #[actix_rt::test]
+    async fn writes_are_open_without_a_write_token() {
+        let (status, _) = call(None, TestRequest::post()).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = call(None, post_with("Bearer anything")).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[test]
+    fn tokens_of_different_lengths_never_match() {
+        assert!(tokens_match("secret", "secret"));
+        assert!(!tokens_match("secret", "secre"));
+        assert!(!tokens_match("secret", "secret "));
+        assert!(!tokens_match("secret", ""));
+        assert!(!tokens_match("", "secret"));
+        assert!(!tokens_match("secret", "sekret"));
+    }
+}
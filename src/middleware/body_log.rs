@@ -0,0 +1,227 @@
+use crate::config::BodyLogConfig;
+use actix_web::body::{self, BodySize, BoxBody, MessageBody};
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::error::ErrorInternalServerError;
+use actix_web::web::Bytes;
+use actix_web::Error;
+use futures::future::{ok, LocalBoxFuture, Ready};
+use serde_json::Value;
+use std::rc::Rc;
+use std::sync::Arc;
+
+const REDACTED: &str = "[REDACTED]";
+
+// Matches `/api/{version}/data` and everything below it.
+fn is_data_route(path: &str) -> bool {
+    let mut segments = path.split('/').skip(1);
+    segments.next() == Some("api") && segments.nth(1) == Some("data")
+}
+
+// Streamed bodies (event streams, NDJSON and CSV exports) are passed through untouched, since
+// buffering them for logging would hold the whole response in memory or never finish.
+fn is_streamed<B: MessageBody>(body: &B) -> bool {
+    matches!(body.size(), BodySize::Stream)
+}
+
+fn redact(value: &mut Value, fields: &[String]) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if fields.iter().any(|field| field.eq_ignore_ascii_case(key)) {
+                    *value = Value::String(REDACTED.to_owned());
+                } else {
+                    redact(value, fields);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(|value| redact(value, fields)),
+        _ => {}
+    }
+}
+
+// Bodies that aren't JSON are summarized rather than logged, since they can't be redacted.
+fn render(body: &Bytes, config: &BodyLogConfig) -> String {
+    if body.is_empty() {
+        return "<empty>".to_owned();
+    }
+    let mut value = match serde_json::from_slice::<Value>(body) {
+        Ok(value) => value,
+        Err(_) => return format!("<{} bytes, not JSON>", body.len()),
+    };
+    redact(&mut value, &config.redact);
+    let mut rendered = value.to_string();
+    if rendered.len() > config.max_bytes {
+        let mut end = config.max_bytes;
+        while !rendered.is_char_boundary(end) {
+            end -= 1;
+        }
+        rendered.truncate(end);
+        rendered.push_str("...(truncated)");
+    }
+    rendered
+}
+
+pub struct BodyLog(Arc<BodyLogConfig>);
+
+impl BodyLog {
+    pub fn new(config: Arc<BodyLogConfig>) -> Self {
+        Self(config)
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for BodyLog
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Transform = BodyLogMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(BodyLogMiddleware {
+            service: Rc::new(service),
+            config: self.0.clone(),
+        })
+    }
+}
+
+pub struct BodyLogMiddleware<S> {
+    service: Rc<S>,
+    config: Arc<BodyLogConfig>,
+}
+
+impl<S, B> Service<ServiceRequest> for BodyLogMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        if !is_data_route(req.path()) {
+            let fut = self.service.call(req);
+            return Box::pin(async move { fut.await.map(ServiceResponse::map_into_boxed_body) });
+        }
+
+        let service = self.service.clone();
+        let config = self.config.clone();
+        Box::pin(async move {
+            let method = req.method().clone();
+            let path = req.path().to_owned();
+            // Buffer the request body so it can be logged, then hand the same bytes back to the
+            // handler.
+            let request_body = req.extract::<Bytes>().await?;
+            tracing::info!(%method, %path, body = %render(&request_body, &config), "Request body");
+            req.set_payload(request_body.into());
+
+            let (req, res) = service.call(req).await?.into_parts();
+            if is_streamed(res.body()) {
+                return Ok(ServiceResponse::new(req, res.map_into_boxed_body()));
+            }
+            let (res, response_body) = res.into_parts();
+            let response_body = body::to_bytes(response_body).await.map_err(|e| {
+                let e: Box<dyn std::error::Error> = e.into();
+                ErrorInternalServerError(e.to_string())
+            })?;
+            tracing::info!(
+                %method,
+                %path,
+                status = res.status().as_u16(),
+                body = %render(&response_body, &config),
+                "Response body"
+            );
+            let res = res.set_body(response_body).map_into_boxed_body();
+            Ok(ServiceResponse::new(req, res))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::{web, App, HttpResponse};
+    use serde_json::json;
+
+    fn config(max_bytes: usize) -> BodyLogConfig {
+        BodyLogConfig {
+            redact: vec!["password".to_owned()],
+            max_bytes,
+        }
+    }
+
+    #[test]
+    fn matches_only_data_routes() {
+        assert!(is_data_route("/api/v1/data"));
+        assert!(is_data_route("/api/v2/data/7"));
+        assert!(!is_data_route("/api/v1/other"));
+        assert!(!is_data_route("/graphql"));
+    }
+
+    #[test]
+    fn detects_streamed_bodies() {
+        assert!(!is_streamed(&Bytes::from_static(b"{}")));
+        assert!(!is_streamed(&()));
+        let stream = futures::stream::iter([Ok::<_, Error>(Bytes::from_static(b"{}\n"))]);
+        assert!(is_streamed(&body::BodyStream::new(stream)));
+    }
+
+    #[test]
+    fn redacts_nested_fields() {
+        let body =
+            Bytes::from(json!({"name": "Paul", "auth": [{"PASSWORD": "hunter2"}]}).to_string());
+        let rendered = render(&body, &config(1024));
+        assert!(!rendered.contains("hunter2"));
+        assert!(rendered.contains(REDACTED));
+        assert!(rendered.contains("Paul"));
+    }
+
+    #[test]
+    fn truncates_long_bodies() {
+        let body = Bytes::from(json!({"name": "a".repeat(100)}).to_string());
+        let rendered = render(&body, &config(10));
+        assert_eq!(rendered, "{\"name\":\"a...(truncated)");
+    }
+
+    #[actix_rt::test]
+    async fn leaves_csv_exports_unbuffered() {
+        let app = init_service(
+            App::new()
+                .wrap(BodyLog::new(Arc::new(config(1024))))
+                .route(
+                    "/api/v1/data/export.csv",
+                    web::get().to(|| async {
+                        let records = futures::stream::iter([Ok::<_, Error>(Bytes::from_static(
+                            b"id,name\r\n",
+                        ))]);
+                        HttpResponse::Ok()
+                            .content_type("text/csv")
+                            .streaming(records)
+                    }),
+                )
+                .route(
+                    "/api/v1/data",
+                    web::get().to(|| async { HttpResponse::Ok().json(json!([])) }),
+                ),
+        )
+        .await;
+
+        let req = TestRequest::get()
+            .uri("/api/v1/data/export.csv")
+            .to_request();
+        let res = call_service(&app, req).await;
+        assert!(is_streamed(res.response().body()));
+
+        // JSON bodies are still buffered so they can be logged.
+        let req = TestRequest::get().uri("/api/v1/data").to_request();
+        let res = call_service(&app, req).await;
+        assert!(!is_streamed(res.response().body()));
+    }
+}
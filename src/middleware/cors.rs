@@ -0,0 +1,126 @@
+use crate::middleware::request_id::REQUEST_ID;
+use crate::middleware::response_time::RESPONSE_TIME;
+use actix_cors::Cors;
+use actix_web::http::header::{self, HeaderName};
+use actix_web::http::Method;
+
+// Only the listed origins may call the API from a browser. Allows the methods the data routes use
+// and exposes the headers they set, so cross-origin clients can read them.
+pub fn cors(origins: &[String]) -> Cors {
+    origins
+        .iter()
+        .fold(Cors::default(), |cors, origin| cors.allowed_origin(origin))
+        .allowed_methods([
+            Method::GET,
+            Method::HEAD,
+            Method::POST,
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE,
+        ])
+        .allowed_headers([
+            header::ACCEPT,
+            header::AUTHORIZATION,
+            header::CONTENT_TYPE,
+            header::IF_NONE_MATCH,
+            HeaderName::from_static("idempotency-key"),
+            REQUEST_ID,
+        ])
+        .expose_headers([
+            header::ETAG,
+            header::LOCATION,
+            header::RETRY_AFTER,
+            HeaderName::from_static("idempotent-replayed"),
+            REQUEST_ID,
+            RESPONSE_TIME,
+        ])
+        .max_age(3600)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::{self, TestRequest};
+    use actix_web::{web, App, HttpResponse};
+
+    const ORIGIN: &str = "https://app.example.com";
+
+    // Sends a preflight for `method` with `headers`, and returns the response's CORS headers.
+    async fn preflight(method: &str, headers: &str) -> (Option<String>, Option<String>) {
+        let app = test::init_service(
+            App::new()
+                .wrap(cors(&[ORIGIN.to_owned()]))
+                .route("/api/v1/data/{id}", web::to(HttpResponse::Ok)),
+        )
+        .await;
+        let req = TestRequest::default()
+            .method(Method::OPTIONS)
+            .uri("/api/v1/data/1")
+            .insert_header((header::ORIGIN, ORIGIN))
+            .insert_header((header::ACCESS_CONTROL_REQUEST_METHOD, method))
+            .insert_header((header::ACCESS_CONTROL_REQUEST_HEADERS, headers))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert!(
+            res.status().is_success(),
+            "preflight failed: {}",
+            res.status()
+        );
+        let value = |name| {
+            res.headers()
+                .get(name)
+                .map(|value: &header::HeaderValue| value.to_str().unwrap().to_owned())
+        };
+        (
+            value(header::ACCESS_CONTROL_ALLOW_METHODS),
+            value(header::ACCESS_CONTROL_ALLOW_HEADERS),
+        )
+    }
+
+    // Sends a cross-origin request, and returns the headers the response exposes.
+    async fn exposed() -> String {
+        let app = test::init_service(
+            App::new()
+                .wrap(cors(&[ORIGIN.to_owned()]))
+                .route("/api/v1/data/{id}", web::to(HttpResponse::Ok)),
+        )
+        .await;
+        let req = TestRequest::get()
+            .uri("/api/v1/data/1")
+            .insert_header((header::ORIGIN, ORIGIN))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        res.headers()
+            .get(header::ACCESS_CONTROL_EXPOSE_HEADERS)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_owned()
+    }
+
+    #[actix_rt::test]
+    async fn allows_json_patch_requests() {
+        let (methods, headers) = preflight("PATCH", "content-type").await;
+        assert!(methods.unwrap().contains("PATCH"));
+        assert!(headers.unwrap().contains("content-type"));
+    }
+
+    #[actix_rt::test]
+    async fn allows_idempotent_retries() {
+        let (_, headers) = preflight("POST", "content-type, idempotency-key").await;
+        assert!(headers.unwrap().contains("idempotency-key"));
+        assert!(exposed().await.contains("idempotent-replayed"));
+    }
+
+    #[actix_rt::test]
+    async fn shares_request_ids() {
+        let (_, headers) = preflight("GET", "x-request-id").await;
+        assert!(headers.unwrap().contains("x-request-id"));
+        assert!(exposed().await.contains("x-request-id"));
+    }
+
+    #[actix_rt::test]
+    async fn exposes_response_times() {
+        assert!(exposed().await.contains("x-response-time-ms"));
+    }
+}
@@ -0,0 +1,8 @@
+pub mod auth;
+pub mod body_log;
+pub mod cors;
+pub mod metrics;
+pub mod rate_limit;
+pub mod request_id;
+pub mod response_time;
+pub mod timeout;
@@ -0,0 +1,121 @@
+use crate::services::error::Error as ServiceError;
+use actix_web::body::{BoxBody, EitherBody};
+use actix_web::dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{self, HeaderName, HeaderValue};
+use actix_web::{Error, FromRequest, HttpMessage, HttpRequest};
+use futures::future::{ok, ready, LocalBoxFuture, Ready};
+
+pub(crate) const REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+// The id of the current request, taken from `X-Request-Id` or generated. Handlers can extract it,
+// and it is recorded as `request_id` on the request's tracing span, which services log under.
+#[derive(Clone, Debug)]
+pub struct RequestId(String);
+
+impl RequestId {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromRequest for RequestId {
+    type Error = Error;
+    type Future = Ready<Result<Self, Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let id = req.extensions().get::<RequestId>().cloned();
+        ready(id.ok_or_else(|| {
+            actix_web::error::ErrorInternalServerError(
+                "RequestIdHeader middleware is not registered",
+            )
+        }))
+    }
+}
+
+// Must be registered directly inside `TracingLogger`, which generates the id used when the client
+// doesn't send one.
+pub struct RequestIdHeader;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestIdHeader
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RequestIdHeaderMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(RequestIdHeaderMiddleware { service })
+    }
+}
+
+pub struct RequestIdHeaderMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestIdHeaderMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let id = match incoming_id(&req) {
+            Some(id) => {
+                // Still inside the root span `TracingLogger` entered, so logs use the client's id.
+                tracing::Span::current().record("request_id", id.as_str());
+                id
+            }
+            None => req
+                .extensions()
+                .get::<tracing_actix_web::RequestId>()
+                .map(ToString::to_string)
+                .unwrap_or_default(),
+        };
+        req.extensions_mut().insert(RequestId(id.clone()));
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let mut res = fut.await?;
+            if let Ok(value) = HeaderValue::from_str(&id) {
+                res.headers_mut().insert(REQUEST_ID, value);
+            }
+            // Service errors are rendered without access to the request, so their body is
+            // rendered again here with the id included.
+            let body = res
+                .response()
+                .error()
+                .and_then(|e| e.as_error::<ServiceError>())
+                .and_then(|e| {
+                    let mut dto = e.to_dto();
+                    dto.request_id = Some(id);
+                    serde_json::to_vec(&dto).ok()
+                });
+            Ok(match body {
+                Some(body) => res.map_body(|head, _| {
+                    // Compression has already run, and the new body is plain JSON.
+                    head.headers_mut().remove(header::CONTENT_ENCODING);
+                    EitherBody::right(BoxBody::new(body))
+                }),
+                None => res.map_into_left_body(),
+            })
+        })
+    }
+}
+
+// Ids from clients are echoed back in a header and logs, so only short printable ones are trusted.
+fn incoming_id(req: &ServiceRequest) -> Option<String> {
+    let id = req.headers().get(REQUEST_ID)?.to_str().ok()?;
+    if id.is_empty() || id.len() > MAX_REQUEST_ID_LEN || !id.bytes().all(|b| b.is_ascii_graphic()) {
+        return None;
+    }
+    Some(id.to_owned())
+}
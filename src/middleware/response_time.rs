@@ -0,0 +1,57 @@
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::Error;
+use futures::future::{ok, LocalBoxFuture, Ready};
+use std::time::Instant;
+
+pub(crate) const RESPONSE_TIME: HeaderName = HeaderName::from_static("x-response-time-ms");
+
+// Adds `X-Response-Time-Ms` to every response. Registered innermost so the measurement covers the
+// handler rather than the other middleware.
+pub struct ResponseTime;
+
+impl<S, B> Transform<S, ServiceRequest> for ResponseTime
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = ResponseTimeMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(ResponseTimeMiddleware { service })
+    }
+}
+
+pub struct ResponseTimeMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for ResponseTimeMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let start = Instant::now();
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let mut res = fut.await?;
+            // Fractional milliseconds, since most handlers finish in well under one.
+            let elapsed = start.elapsed().as_secs_f64() * 1000.0;
+            if let Ok(value) = HeaderValue::from_str(&format!("{:.3}", elapsed)) {
+                res.headers_mut().insert(RESPONSE_TIME, value);
+            }
+            Ok(res)
+        })
+    }
+}
@@ -0,0 +1,77 @@
+use crate::dtos::error::ErrorDto;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::error::InternalError;
+use actix_web::{Error, HttpResponse};
+use futures::future::{ok, LocalBoxFuture, Ready};
+use std::time::Duration;
+
+pub struct Timeout(Duration);
+
+impl Timeout {
+    pub fn new(timeout: Duration) -> Self {
+        Self(timeout)
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for Timeout
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = TimeoutMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(TimeoutMiddleware {
+            service,
+            timeout: self.0,
+        })
+    }
+}
+
+pub struct TimeoutMiddleware<S> {
+    service: S,
+    timeout: Duration,
+}
+
+impl<S, B> Service<ServiceRequest> for TimeoutMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let path = req.path().to_owned();
+        let timeout = self.timeout;
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            // Timing out drops the handler's future, cancelling whatever it was waiting on. The
+            // request went with it, so the 504 is returned as an error for actix to render.
+            match actix_rt::time::timeout(timeout, fut).await {
+                Ok(res) => res,
+                Err(_) => {
+                    tracing::warn!(
+                        path,
+                        timeout_ms = timeout.as_millis() as u64,
+                        "Request timed out"
+                    );
+                    let response = HttpResponse::GatewayTimeout().json(ErrorDto {
+                        error: "Request timed out".to_owned(),
+                        code: "TIMEOUT",
+                        errors: None,
+                        request_id: None,
+                    });
+                    Err(InternalError::from_response("Request timed out", response).into())
+                }
+            }
+        })
+    }
+}
@@ -1,4 +1,55 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+// The primary key of a `data` row. Stored and serialized as the bare `BIGINT`, but a distinct type
+// so ids of other tables can't be passed where a data id is expected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct DataId(i64);
+
+impl From<i64> for DataId {
+    fn from(id: i64) -> Self {
+        Self(id)
+    }
+}
+
+impl From<DataId> for i64 {
+    fn from(id: DataId) -> Self {
+        id.0
+    }
+}
+
+impl FromStr for DataId {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Self)
+    }
+}
+
+impl fmt::Display for DataId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[derive(Clone)]
 pub struct Data {
-    pub(crate) id: i64,
+    pub(crate) id: DataId,
     pub(crate) name: String,
+    pub(crate) created_at: DateTime<Utc>,
+    pub(crate) updated_at: DateTime<Utc>,
+    pub(crate) deleted_at: Option<DateTime<Utc>>,
+}
+
+// Summary of the live rows. Everything but `count` is `None` when there are none.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DataStats {
+    pub count: i64,
+    pub min_id: Option<i64>,
+    pub max_id: Option<i64>,
+    pub avg_name_length: Option<f64>,
 }
@@ -0,0 +1,19 @@
+#[derive(Clone, Copy, Debug, Default)]
+pub enum SortField {
+    #[default]
+    Id,
+    Name,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Sort {
+    pub(crate) field: SortField,
+    pub(crate) order: SortOrder,
+}
@@ -1,31 +1,324 @@
-use coi::{Inject, Provide};
+use crate::config::PoolConfig;
+use crate::metrics::MetricsRegistry;
+use crate::repositories::error::Error;
+use coi::{Container, Inject, Provide};
+use futures::future::join_all;
+#[cfg(not(feature = "tls"))]
+use mobc_postgres::tokio_postgres::NoTls;
+use mobc_postgres::tokio_postgres::{Config as PgConfig, Error as PostgresError};
 use mobc_postgres::{
-    mobc::{Connection, Error as MobcError, Manager, Pool},
+    mobc::{Connection, Error as MobcError, Manager, Pool, State},
     PgConnectionManager,
 };
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const STARTUP_BACKOFF_START: Duration = Duration::from_millis(100);
+const STARTUP_BACKOFF_MAX: Duration = Duration::from_secs(5);
+// How long a timed out checkout waits to read the pool's state for the circuit breaker.
+const STATE_TIMEOUT: Duration = Duration::from_millis(100);
+
+// The connector every pool is built with, so the server only has one set of pool, repository and
+// service types to compile. Whether a connection actually uses TLS is still up to `PGSSLMODE`.
+#[cfg(feature = "tls")]
+pub type AppTls = postgres_openssl::MakeTlsConnector;
+#[cfg(not(feature = "tls"))]
+pub type AppTls = NoTls;
+
+#[derive(Clone, Copy)]
+pub struct RetryPolicy {
+    pub(crate) retries: u32,
+    pub(crate) base_delay: Duration,
+}
+
+// After `threshold` checkouts in a row have failed, further ones fail straight away for `cooldown`
+// instead of each waiting on a database that is down. A `threshold` of `0` disables the breaker.
+#[derive(Clone, Copy)]
+pub struct BreakerPolicy {
+    pub(crate) threshold: u32,
+    pub(crate) cooldown: Duration,
+}
+
+enum BreakerState {
+    Closed { failures: u32 },
+    Open { until: Instant },
+}
+
+struct CircuitBreaker {
+    policy: BreakerPolicy,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    fn new(policy: BreakerPolicy) -> Self {
+        Self {
+            policy,
+            state: Mutex::new(BreakerState::Closed { failures: 0 }),
+        }
+    }
+
+    // Once the cooldown has passed, the next caller is let through to probe the database, and the
+    // rest keep failing until it succeeds or another cooldown has passed.
+    fn allow(&self) -> bool {
+        self.allow_at(Instant::now())
+    }
+
+    fn allow_at(&self, now: Instant) -> bool {
+        let mut state = self.state.lock().unwrap();
+        match *state {
+            BreakerState::Closed { .. } => true,
+            BreakerState::Open { until } if now >= until => {
+                *state = BreakerState::Open {
+                    until: now + self.policy.cooldown,
+                };
+                true
+            }
+            BreakerState::Open { .. } => false,
+        }
+    }
+
+    fn record(&self, pool: &'static str, succeeded: bool) {
+        self.record_at(pool, succeeded, Instant::now())
+    }
+
+    // A checkout that timed out with connections open only means they were all busy, which a slow
+    // database causes too. With none open, connecting itself is hanging, so it counts as a failure.
+    fn record_timeout(&self, pool: &'static str, open_connections: u64) {
+        self.record_timeout_at(pool, open_connections, Instant::now())
+    }
+
+    fn record_timeout_at(&self, pool: &'static str, open_connections: u64, now: Instant) {
+        if open_connections == 0 {
+            self.record_at(pool, false, now);
+        }
+    }
+
+    fn record_at(&self, pool: &'static str, succeeded: bool, now: Instant) {
+        if self.policy.threshold == 0 {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        match (&mut *state, succeeded) {
+            (BreakerState::Closed { failures }, true) => *failures = 0,
+            (BreakerState::Open { .. }, true) => {
+                tracing::info!(pool, "Database reachable again, closing circuit breaker");
+                *state = BreakerState::Closed { failures: 0 };
+            }
+            (BreakerState::Closed { failures }, false) if *failures + 1 < self.policy.threshold => {
+                *failures += 1;
+            }
+            (_, false) => {
+                tracing::warn!(
+                    pool,
+                    cooldown = ?self.policy.cooldown,
+                    "Connections keep failing, opening circuit breaker"
+                );
+                *state = BreakerState::Open {
+                    until: now + self.policy.cooldown,
+                };
+            }
+        }
+    }
+}
+
+pub struct PoolState {
+    pub(crate) max_open: u64,
+    pub(crate) connections: u64,
+    pub(crate) in_use: u64,
+    pub(crate) idle: u64,
+    pub(crate) wait_count: u64,
+    pub(crate) wait_duration: Duration,
+    pub(crate) max_idle_closed: u64,
+    pub(crate) max_lifetime_closed: u64,
+}
+
+impl PoolState {
+    // Connections currently checked out of the pool.
+    pub fn in_use(&self) -> u64 {
+        self.in_use
+    }
+}
+
+impl From<State> for PoolState {
+    fn from(state: State) -> Self {
+        Self {
+            max_open: state.max_open,
+            connections: state.connections,
+            in_use: state.in_use,
+            idle: state.idle,
+            wait_count: state.wait_count,
+            wait_duration: state.wait_duration,
+            max_idle_closed: state.max_idle_closed,
+            max_lifetime_closed: state.max_lifetime_closed,
+        }
+    }
+}
+
+pub fn build_pool<T>(
+    config: PgConfig,
+    tls: T,
+    pool_config: &PoolConfig,
+) -> Pool<PgConnectionManager<T>>
+where
+    PgConnectionManager<T>: Manager,
+{
+    Pool::builder()
+        .max_open(pool_config.max_open)
+        .max_idle(pool_config.max_idle)
+        .get_timeout(Some(pool_config.timeout))
+        .max_lifetime(pool_config.max_lifetime)
+        .max_idle_lifetime(pool_config.idle_timeout)
+        .build(PgConnectionManager::new(config, tls))
+}
+
+// Retries opening a connection with exponential backoff until one succeeds or `timeout` has
+// passed, for when the server starts before the database accepts connections.
+pub async fn wait_for_database<T>(
+    name: &'static str,
+    pool: &Pool<PgConnectionManager<T>>,
+    timeout: Duration,
+) -> Result<(), MobcError<<PgConnectionManager<T> as Manager>::Error>>
+where
+    PgConnectionManager<T>: Manager,
+    <PgConnectionManager<T> as Manager>::Error: std::fmt::Display,
+{
+    let start = Instant::now();
+    let mut delay = STARTUP_BACKOFF_START;
+    loop {
+        match pool.get().await {
+            Ok(_) => return Ok(()),
+            Err(e) if start.elapsed() + delay < timeout => {
+                tracing::warn!(
+                    pool = name,
+                    ?delay,
+                    "Database unavailable ({}), retrying",
+                    e
+                );
+                actix_rt::time::sleep(delay).await;
+                delay = (delay * 2).min(STARTUP_BACKOFF_MAX);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// Opens `POOL_WARMUP` connections at once and hands them back to the pool as idle connections, so
+// the first requests don't pay for connection setup. Returns how many were opened, or the last error
+// if none could be.
+pub async fn warm_up<T>(
+    name: &'static str,
+    pool: &Pool<PgConnectionManager<T>>,
+    pool_config: &PoolConfig,
+) -> Result<usize, MobcError<<PgConnectionManager<T> as Manager>::Error>>
+where
+    PgConnectionManager<T>: Manager,
+{
+    let count = pool_config.warmup;
+    if count == 0 {
+        return Ok(0);
+    }
+    let results = join_all((0..count).map(|_| pool.get())).await;
+    let mut opened = 0;
+    let mut last_error = None;
+    for result in results {
+        match result {
+            Ok(_) => opened += 1,
+            Err(e) => last_error = Some(e),
+        }
+    }
+    match last_error {
+        Some(e) if opened == 0 => Err(e),
+        _ => {
+            tracing::info!(pool = name, opened, requested = count, "Warmed up pool");
+            Ok(opened)
+        }
+    }
+}
 
 #[derive(Inject)]
-pub struct PostgresPool<T>(Pool<PgConnectionManager<T>>)
+pub struct PostgresPool<T>(
+    Pool<PgConnectionManager<T>>,
+    RetryPolicy,
+    &'static str,
+    Arc<MetricsRegistry>,
+    CircuitBreaker,
+)
 where
     PgConnectionManager<T>: Manager;
 
+impl<T> PostgresPool<T>
+where
+    PgConnectionManager<T>: Manager<Error = PostgresError>,
+{
+    pub async fn get(&self) -> Result<Connection<PgConnectionManager<T>>, Error> {
+        if !self.4.allow() {
+            return Err(Error::CircuitOpen);
+        }
+        // Slow acquisitions mean requests are queueing for connections, so the wait is recorded
+        // whether or not a connection was eventually handed out.
+        let start = Instant::now();
+        let mut attempt = 0;
+        let result = loop {
+            match self.0.get().await {
+                // Waiting again on a pool that is already exhausted would only add to the queue, so
+                // only failed connections are retried.
+                Err(MobcError::Timeout) => break Err(MobcError::Timeout),
+                Err(_) if attempt < self.1.retries => {
+                    // Back off exponentially: base, 2 * base, 4 * base, ...
+                    let delay = self
+                        .1
+                        .base_delay
+                        .saturating_mul(2u32.saturating_pow(attempt));
+                    attempt += 1;
+                    tracing::warn!(attempt, ?delay, "Retrying pool connection");
+                    actix_rt::time::sleep(delay).await;
+                }
+                result => break result,
+            }
+        };
+        self.3.observe_pool_acquire(self.2, start.elapsed());
+        match &result {
+            Ok(_) => self.4.record(self.2, true),
+            Err(MobcError::Timeout) => {
+                let open_connections = self.open_connections().await;
+                self.4.record_timeout(self.2, open_connections);
+            }
+            Err(_) => self.4.record(self.2, false),
+        }
+        Ok(result?)
+    }
+
+    // mobc opens connections while holding the lock that `state()` waits on, so a state that can't
+    // be read straight away means another checkout is stuck connecting, same as having none open.
+    async fn open_connections(&self) -> u64 {
+        match actix_rt::time::timeout(STATE_TIMEOUT, self.0.state()).await {
+            Ok(state) => state.connections,
+            Err(_) => 0,
+        }
+    }
+}
+
 impl<T> PostgresPool<T>
 where
     PgConnectionManager<T>: Manager,
 {
-    pub async fn get(
-        &self,
-    ) -> Result<
-        Connection<PgConnectionManager<T>>,
-        MobcError<<PgConnectionManager<T> as Manager>::Error>,
-    > {
-        self.0.get().await
+    pub async fn state(&self) -> PoolState {
+        self.0.state().await.into()
+    }
+
+    // Closes all idle connections and stops the pool from keeping any returned ones around.
+    pub async fn drain(&self) {
+        self.0.set_max_idle_conns(0).await;
     }
 }
 
-#[derive(Provide)]
-#[coi(provides PostgresPool<T> with PostgresPool(self.0.clone()))]
-pub struct PostgresPoolProvider<T>(Pool<PgConnectionManager<T>>)
+// `name` labels the pool's metrics, and should match the key it is registered under.
+pub struct PostgresPoolProvider<T>(
+    &'static str,
+    Pool<PgConnectionManager<T>>,
+    RetryPolicy,
+    BreakerPolicy,
+)
 where
     PgConnectionManager<T>: Manager;
 
@@ -33,7 +326,170 @@ impl<T> PostgresPoolProvider<T>
 where
     PgConnectionManager<T>: Manager,
 {
-    pub fn new(pool: Pool<PgConnectionManager<T>>) -> Self {
-        Self(pool)
+    pub fn new(
+        name: &'static str,
+        pool: Pool<PgConnectionManager<T>>,
+        retry: RetryPolicy,
+        breaker: BreakerPolicy,
+    ) -> Self {
+        Self(name, pool, retry, breaker)
+    }
+}
+
+impl<T> Provide for PostgresPoolProvider<T>
+where
+    T: Send + Sync + 'static,
+    PgConnectionManager<T>: Manager,
+{
+    type Output = PostgresPool<T>;
+
+    fn provide(&self, container: &Container) -> coi::Result<Arc<PostgresPool<T>>> {
+        let registry = container.resolve::<MetricsRegistry>("registry")?;
+        Ok(Arc::new(PostgresPool(
+            self.1.clone(),
+            self.2,
+            self.0,
+            registry,
+            CircuitBreaker::new(self.3),
+        )))
+    }
+
+    #[cfg(feature = "debug")]
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["registry"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::MetricsRegistryProvider;
+    use coi::container;
+    use mobc_postgres::tokio_postgres::NoTls;
+
+    const COOLDOWN: Duration = Duration::from_secs(10);
+
+    fn breaker(threshold: u32) -> CircuitBreaker {
+        CircuitBreaker::new(BreakerPolicy {
+            threshold,
+            cooldown: COOLDOWN,
+        })
+    }
+
+    #[test]
+    fn opens_after_threshold_failures_in_a_row() {
+        let breaker = breaker(3);
+        let now = Instant::now();
+        breaker.record_at("pool", false, now);
+        breaker.record_at("pool", false, now);
+        assert!(breaker.allow_at(now));
+        breaker.record_at("pool", true, now);
+        breaker.record_at("pool", false, now);
+        breaker.record_at("pool", false, now);
+        assert!(breaker.allow_at(now), "a success resets the count");
+        breaker.record_at("pool", false, now);
+        assert!(!breaker.allow_at(now));
+        assert!(!breaker.allow_at(now + COOLDOWN / 2));
+    }
+
+    #[test]
+    fn lets_one_probe_through_after_the_cooldown() {
+        let breaker = breaker(1);
+        let now = Instant::now();
+        breaker.record_at("pool", false, now);
+        let later = now + COOLDOWN;
+        assert!(breaker.allow_at(later));
+        assert!(!breaker.allow_at(later), "only one probe per cooldown");
+        assert!(breaker.allow_at(later + COOLDOWN));
+    }
+
+    #[test]
+    fn closes_when_the_probe_succeeds() {
+        let breaker = breaker(1);
+        let now = Instant::now();
+        breaker.record_at("pool", false, now);
+        let later = now + COOLDOWN;
+        assert!(breaker.allow_at(later));
+        breaker.record_at("pool", true, later);
+        assert!(breaker.allow_at(later));
+        assert!(breaker.allow_at(later));
+    }
+
+    #[test]
+    fn reopens_when_the_probe_fails() {
+        let breaker = breaker(2);
+        let now = Instant::now();
+        breaker.record_at("pool", false, now);
+        breaker.record_at("pool", false, now);
+        let later = now + COOLDOWN;
+        assert!(breaker.allow_at(later));
+        breaker.record_at("pool", false, later);
+        assert!(!breaker.allow_at(later));
+        assert!(!breaker.allow_at(later + COOLDOWN / 2));
+        assert!(breaker.allow_at(later + COOLDOWN));
+    }
+
+    #[test]
+    fn threshold_of_zero_never_opens() {
+        let breaker = breaker(0);
+        let now = Instant::now();
+        for _ in 0..100 {
+            breaker.record_at("pool", false, now);
+        }
+        assert!(breaker.allow_at(now));
+    }
+
+    #[test]
+    fn timeouts_count_only_without_open_connections() {
+        let breaker = breaker(2);
+        let now = Instant::now();
+        for _ in 0..10 {
+            breaker.record_timeout_at("pool", 3, now);
+        }
+        assert!(breaker.allow_at(now), "a busy pool is not a failing one");
+        breaker.record_timeout_at("pool", 0, now);
+        assert!(breaker.allow_at(now));
+        breaker.record_timeout_at("pool", 0, now);
+        assert!(!breaker.allow_at(now));
+    }
+
+    #[actix_rt::test]
+    async fn opens_when_connecting_hangs() {
+        // Accepts connections but never answers, like a database that has stopped responding.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut pg_config = PgConfig::new();
+        pg_config
+            .host("127.0.0.1")
+            .port(listener.local_addr().unwrap().port())
+            .user("postgres");
+        let pool_config = PoolConfig {
+            max_open: 2,
+            max_idle: 2,
+            timeout: Duration::from_millis(50),
+            warmup: 0,
+            max_lifetime: None,
+            idle_timeout: None,
+        };
+        let retry = RetryPolicy {
+            retries: 0,
+            base_delay: Duration::ZERO,
+        };
+        let breaker = BreakerPolicy {
+            threshold: 2,
+            cooldown: COOLDOWN,
+        };
+        let pool = build_pool(pg_config, NoTls, &pool_config);
+        let container = container! {
+            registry => MetricsRegistryProvider; singleton,
+            pool => PostgresPoolProvider::new("pool", pool, retry, breaker); singleton,
+        };
+        let pool = container.resolve::<PostgresPool<NoTls>>("pool").unwrap();
+
+        for _ in 0..2 {
+            let error = pool.get().await.err().unwrap();
+            assert!(matches!(error, Error::PoolTimeout));
+        }
+        let error = pool.get().await.err().unwrap();
+        assert!(matches!(error, Error::CircuitOpen));
     }
 }
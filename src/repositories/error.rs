@@ -1,13 +1,100 @@
+use crate::models::data::DataId;
 use mobc_postgres::mobc::Error as MobcError;
+use mobc_postgres::tokio_postgres::error::SqlState;
 use mobc_postgres::tokio_postgres::Error as PostgresError;
 // use serde_tokio_postgres::Error as SerdeError;
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("Pool error: {0}")]
-    Mobc(#[from] MobcError<PostgresError>),
-    #[error("Postgress error: {0}")]
-    Postgres(#[from] PostgresError),
+    Pool(#[source] MobcError<PostgresError>),
+    #[error("Timed out waiting for a pooled connection")]
+    PoolTimeout,
+    #[error("Database unavailable, circuit breaker is open")]
+    CircuitOpen,
+    #[error("Query error: {0}")]
+    Query(#[source] PostgresError),
+    #[error("Query timed out")]
+    QueryTimeout,
+    #[error("Foreign key violation: {}", db_message(.0))]
+    ForeignKeyViolation(#[source] PostgresError),
+    #[error("Unique violation: {}", db_message(.0))]
+    UniqueViolation(#[source] PostgresError),
+    // The in-memory repository's equivalent of `UniqueViolation` on `data.name`.
+    #[error("Unique violation: name `{0}` already exists")]
+    DuplicateName(String),
+    #[error("No matching row found")]
+    NotFound,
+    #[error("Invalid row {0}: {1}")]
+    InvalidRow(DataId, &'static str),
+    #[error("Invalid name: {0}")]
+    InvalidName(&'static str),
+    #[cfg(feature = "sqlx")]
+    #[error("Database error: {0}")]
+    Sqlx(#[source] ::sqlx::Error),
     // #[error("Deserialization error: {0}")]
     // Serde(#[from] SerdeError),
 }
+
+impl From<MobcError<PostgresError>> for Error {
+    fn from(e: MobcError<PostgresError>) -> Self {
+        match e {
+            // Every connection is busy, which is overload rather than a broken database.
+            MobcError::Timeout => Error::PoolTimeout,
+            e => Error::Pool(e),
+        }
+    }
+}
+
+#[cfg(feature = "sqlx")]
+impl From<::sqlx::Error> for Error {
+    fn from(e: ::sqlx::Error) -> Self {
+        match e {
+            ::sqlx::Error::PoolTimedOut => Error::PoolTimeout,
+            ::sqlx::Error::RowNotFound => Error::NotFound,
+            ::sqlx::Error::Database(db) if db.code().as_deref() == Some("57014") => {
+                Error::QueryTimeout
+            }
+            e => Error::Sqlx(e),
+        }
+    }
+}
+
+impl Error {
+    // Unique and foreign key violations, which are the client's fault rather than the server's.
+    pub fn is_conflict(&self) -> bool {
+        match self {
+            Error::ForeignKeyViolation(_) | Error::UniqueViolation(_) | Error::DuplicateName(_) => {
+                true
+            }
+            #[cfg(feature = "sqlx")]
+            Error::Sqlx(e) => e.as_database_error().is_some_and(|db| {
+                matches!(
+                    db.kind(),
+                    ::sqlx::error::ErrorKind::UniqueViolation
+                        | ::sqlx::error::ErrorKind::ForeignKeyViolation
+                )
+            }),
+            _ => false,
+        }
+    }
+}
+
+impl From<PostgresError> for Error {
+    fn from(e: PostgresError) -> Self {
+        match e.code() {
+            Some(&SqlState::FOREIGN_KEY_VIOLATION) => Error::ForeignKeyViolation(e),
+            Some(&SqlState::UNIQUE_VIOLATION) => Error::UniqueViolation(e),
+            // Cancelled by the `statement_timeout` set from `QUERY_TIMEOUT_MS`.
+            Some(&SqlState::QUERY_CANCELED) => Error::QueryTimeout,
+            _ => Error::Query(e),
+        }
+    }
+}
+
+// `PostgresError`'s own Display is just "db error", the server's message is the useful part.
+fn db_message(e: &PostgresError) -> String {
+    e.as_db_error()
+        .map(|db| db.message().to_owned())
+        .unwrap_or_else(|| e.to_string())
+}
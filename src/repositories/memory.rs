@@ -0,0 +1,462 @@
+use crate::models::data::{DataId, DataStats};
+use crate::models::sort::{Sort, SortField, SortOrder};
+use crate::repositories::error::Error;
+use crate::repositories::repo::{sanitize_name, DbData, IRepository};
+use async_trait::async_trait;
+use chrono::Utc;
+use coi::{Inject, Provide};
+use futures::stream::{self, BoxStream, StreamExt};
+use std::collections::hash_map::RandomState;
+use std::collections::BTreeMap;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::{Arc, RwLock};
+use tracing::instrument;
+
+const SEARCH_LIMIT: usize = 50;
+
+#[derive(Default)]
+struct Store {
+    next_id: i64,
+    rows: BTreeMap<DataId, DbData>,
+}
+
+impl Store {
+    // Mirrors xtask/src/sql/seed.sql so the demo data matches the docker database.
+    fn seeded() -> Self {
+        let mut store = Self {
+            next_id: 1,
+            ..Self::default()
+        };
+        for name in ["Paul", "Peter", "Santiago", "Oliver"] {
+            store.insert(name);
+        }
+        store
+    }
+
+    fn insert(&mut self, name: &str) -> DbData {
+        let id = DataId::from(self.next_id);
+        self.next_id += 1;
+        let now = Utc::now();
+        let data = DbData {
+            id,
+            name: name.to_owned(),
+            created_at: now,
+            updated_at: now,
+            deleted_at: None,
+        };
+        self.rows.insert(id, data.clone());
+        data
+    }
+
+    // Names are unique across every row, soft-deleted ones included, like the `UNIQUE` constraint
+    // in Postgres. Rows in `renamed` are about to give up their name, so they don't count.
+    fn check_name(&self, name: &str, renamed: &[DataId]) -> Result<(), Error> {
+        let taken = self
+            .rows
+            .values()
+            .any(|data| data.name == name && !renamed.contains(&data.id));
+        if taken {
+            return Err(Error::DuplicateName(name.to_owned()));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Inject)]
+struct InMemoryRepository(Arc<RwLock<Store>>);
+
+#[async_trait]
+impl IRepository for InMemoryRepository {
+    #[instrument(skip(self))]
+    async fn get(&self, id: DataId, include_deleted: bool) -> Result<DbData, Error> {
+        let store = self.0.read().unwrap();
+        store
+            .rows
+            .get(&id)
+            .filter(|data| include_deleted || data.deleted_at.is_none())
+            .cloned()
+            .ok_or(Error::NotFound)
+    }
+
+    #[instrument(skip(self))]
+    async fn exists(&self, id: DataId) -> Result<bool, Error> {
+        let store = self.0.read().unwrap();
+        Ok(store
+            .rows
+            .get(&id)
+            .is_some_and(|data| data.deleted_at.is_none()))
+    }
+
+    #[instrument(skip(self))]
+    async fn get_all(
+        &self,
+        limit: i64,
+        offset: i64,
+        sort: Sort,
+        include_deleted: bool,
+    ) -> Result<Vec<DbData>, Error> {
+        let store = self.0.read().unwrap();
+        let mut rows = store
+            .rows
+            .values()
+            .filter(|data| include_deleted || data.deleted_at.is_none())
+            .collect::<Vec<_>>();
+        // Rows are already in id order, so a stable sort by name keeps ids as the tie-breaker.
+        match sort.field {
+            SortField::Id => {}
+            SortField::Name => rows.sort_by(|a, b| a.name.cmp(&b.name)),
+        }
+        if let SortOrder::Desc = sort.order {
+            rows.reverse();
+        }
+        let data = rows
+            .into_iter()
+            .skip(offset.max(0) as usize)
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect();
+        Ok(data)
+    }
+
+    #[instrument(skip(self))]
+    async fn get_after(&self, after: i64, limit: i64) -> Result<Vec<DbData>, Error> {
+        let store = self.0.read().unwrap();
+        let data = store
+            .rows
+            .range(DataId::from(after.saturating_add(1))..)
+            .map(|(_, data)| data)
+            .filter(|data| data.deleted_at.is_none())
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect();
+        Ok(data)
+    }
+
+    #[instrument(skip(self))]
+    async fn get_range(&self, from: i64, to: i64) -> Result<Vec<DbData>, Error> {
+        let store = self.0.read().unwrap();
+        if from > to {
+            return Ok(vec![]);
+        }
+        let data = store
+            .rows
+            .range(DataId::from(from)..=DataId::from(to))
+            .map(|(_, data)| data)
+            .filter(|data| data.deleted_at.is_none())
+            .cloned()
+            .collect();
+        Ok(data)
+    }
+
+    #[instrument(skip(self))]
+    async fn get_recent(&self, limit: i64) -> Result<Vec<DbData>, Error> {
+        let store = self.0.read().unwrap();
+        let mut rows = store
+            .rows
+            .values()
+            .filter(|data| data.deleted_at.is_none())
+            .collect::<Vec<_>>();
+        rows.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(b.id.cmp(&a.id)));
+        let data = rows
+            .into_iter()
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect();
+        Ok(data)
+    }
+
+    #[instrument(skip(self))]
+    async fn get_random(&self) -> Result<DbData, Error> {
+        let store = self.0.read().unwrap();
+        let rows = store
+            .rows
+            .values()
+            .filter(|data| data.deleted_at.is_none())
+            .collect::<Vec<_>>();
+        if rows.is_empty() {
+            return Err(Error::NotFound);
+        }
+        // Each `RandomState` is seeded randomly, which is enough to pick a demo row without
+        // pulling in `rand`.
+        let index = RandomState::new().build_hasher().finish() as usize % rows.len();
+        Ok(rows[index].clone())
+    }
+
+    #[instrument(skip(self))]
+    async fn stream_all(
+        &self,
+        sort: Sort,
+    ) -> Result<BoxStream<'static, Result<DbData, Error>>, Error> {
+        let data = self.get_all(i64::MAX, 0, sort, false).await?;
+        Ok(stream::iter(data.into_iter().map(Ok)).boxed())
+    }
+
+    #[instrument(skip(self))]
+    async fn get_many(&self, ids: &[i64]) -> Result<Vec<DbData>, Error> {
+        let store = self.0.read().unwrap();
+        let data = store
+            .rows
+            .values()
+            .filter(|data| data.deleted_at.is_none() && ids.contains(&data.id.into()))
+            .cloned()
+            .collect();
+        Ok(data)
+    }
+
+    #[instrument(skip(self))]
+    async fn insert(&self, name: &str) -> Result<DbData, Error> {
+        let name = sanitize_name(name)?;
+        let mut store = self.0.write().unwrap();
+        store.check_name(name, &[])?;
+        Ok(store.insert(name))
+    }
+
+    #[instrument(skip(self))]
+    async fn insert_many(&self, names: &[String]) -> Result<Vec<DbData>, Error> {
+        // Checked up front, so an invalid or duplicate name leaves the store untouched like the
+        // rolled back transaction does for Postgres.
+        let names = names
+            .iter()
+            .map(|name| sanitize_name(name))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut store = self.0.write().unwrap();
+        for (i, name) in names.iter().enumerate() {
+            store.check_name(name, &[])?;
+            if names[..i].contains(name) {
+                return Err(Error::DuplicateName((*name).to_owned()));
+            }
+        }
+        Ok(names.into_iter().map(|name| store.insert(name)).collect())
+    }
+
+    #[instrument(skip(self))]
+    async fn upsert(&self, name: &str) -> Result<DbData, Error> {
+        let name = sanitize_name(name)?;
+        let mut store = self.0.write().unwrap();
+        match store.rows.values_mut().find(|data| data.name == name) {
+            Some(row) => {
+                row.updated_at = Utc::now();
+                row.deleted_at = None;
+                Ok(row.clone())
+            }
+            None => Ok(store.insert(name)),
+        }
+    }
+
+    #[instrument(skip(self))]
+    async fn update(&self, id: DataId, name: &str) -> Result<DbData, Error> {
+        let name = sanitize_name(name)?;
+        let mut store = self.0.write().unwrap();
+        // Like the `UPDATE`, a missing row is reported before a taken name.
+        store
+            .rows
+            .get(&id)
+            .filter(|data| data.deleted_at.is_none())
+            .ok_or(Error::NotFound)?;
+        store.check_name(name, &[id])?;
+        let row = store.rows.get_mut(&id).ok_or(Error::NotFound)?;
+        row.name = name.to_owned();
+        row.updated_at = Utc::now();
+        Ok(row.clone())
+    }
+
+    #[instrument(skip(self))]
+    async fn update_many(&self, ids: &[i64], name: &str) -> Result<u64, Error> {
+        let name = sanitize_name(name)?;
+        let mut store = self.0.write().unwrap();
+        let renamed = store
+            .rows
+            .values()
+            .filter(|data| data.deleted_at.is_none() && ids.contains(&data.id.into()))
+            .map(|data| data.id)
+            .collect::<Vec<_>>();
+        // Giving several rows the same name would break uniqueness between them.
+        if renamed.len() > 1 {
+            return Err(Error::DuplicateName(name.to_owned()));
+        }
+        store.check_name(name, &renamed)?;
+        let now = Utc::now();
+        let mut updated = 0;
+        for row in store
+            .rows
+            .values_mut()
+            .filter(|data| data.deleted_at.is_none() && ids.contains(&data.id.into()))
+        {
+            row.name = name.to_owned();
+            row.updated_at = now;
+            updated += 1;
+        }
+        Ok(updated)
+    }
+
+    #[instrument(skip(self))]
+    async fn delete(&self, id: DataId) -> Result<u64, Error> {
+        let mut store = self.0.write().unwrap();
+        match store.rows.get_mut(&id) {
+            Some(row) if row.deleted_at.is_none() => {
+                row.deleted_at = Some(Utc::now());
+                Ok(1)
+            }
+            _ => Ok(0),
+        }
+    }
+
+    #[instrument(skip(self))]
+    async fn count(&self) -> Result<i64, Error> {
+        let store = self.0.read().unwrap();
+        let count = store
+            .rows
+            .values()
+            .filter(|data| data.deleted_at.is_none())
+            .count();
+        Ok(count as i64)
+    }
+
+    #[instrument(skip(self))]
+    async fn stats(&self) -> Result<DataStats, Error> {
+        let store = self.0.read().unwrap();
+        let live = store
+            .rows
+            .values()
+            .filter(|data| data.deleted_at.is_none())
+            .collect::<Vec<_>>();
+        if live.is_empty() {
+            return Ok(DataStats::default());
+        }
+        // Postgres `LENGTH` counts characters, not bytes.
+        let total_length = live
+            .iter()
+            .map(|data| data.name.chars().count())
+            .sum::<usize>();
+        Ok(DataStats {
+            count: live.len() as i64,
+            min_id: live.iter().map(|data| data.id.into()).min(),
+            max_id: live.iter().map(|data| data.id.into()).max(),
+            avg_name_length: Some(total_length as f64 / live.len() as f64),
+        })
+    }
+
+    #[instrument(skip(self))]
+    async fn search(&self, term: &str) -> Result<Vec<DbData>, Error> {
+        let term = term.to_lowercase();
+        let store = self.0.read().unwrap();
+        let data = store
+            .rows
+            .values()
+            .filter(|data| data.deleted_at.is_none() && data.name.to_lowercase().contains(&term))
+            .take(SEARCH_LIMIT)
+            .cloned()
+            .collect();
+        Ok(data)
+    }
+
+    #[instrument(skip(self))]
+    async fn ping(&self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn reseed(&self) -> Result<i64, Error> {
+        let mut store = self.0.write().unwrap();
+        *store = Store::seeded();
+        Ok(store.rows.len() as i64)
+    }
+}
+
+// Every scope gets its own repository, but they all share the store owned by the provider.
+#[derive(Provide)]
+#[coi(provides dyn IRepository with InMemoryRepository(self.0.clone()))]
+pub struct InMemoryRepositoryProvider(Arc<RwLock<Store>>);
+
+impl InMemoryRepositoryProvider {
+    pub fn new() -> Self {
+        Self(Arc::new(RwLock::new(Store::seeded())))
+    }
+}
+
+impl Default for InMemoryRepositoryProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::repo::MAX_NAME_LENGTH;
+
+    fn repository() -> InMemoryRepository {
+        InMemoryRepository(Arc::new(RwLock::new(Store::seeded())))
+    }
+
+    fn names(repository: &InMemoryRepository) -> Vec<String> {
+        let store = repository.0.read().unwrap();
+        store.rows.values().map(|data| data.name.clone()).collect()
+    }
+
+    #[actix_rt::test]
+    async fn rejects_taken_names_like_postgres() {
+        let repository = repository();
+        let error = repository.insert("Paul").await.err().unwrap();
+        assert!(matches!(error, Error::DuplicateName(_)));
+        assert!(error.is_conflict());
+
+        // Soft-deleted rows keep their name.
+        repository.delete(DataId::from(2)).await.unwrap();
+        let error = repository.insert("Peter").await.err().unwrap();
+        assert!(matches!(error, Error::DuplicateName(_)));
+        let error = repository
+            .update(DataId::from(1), "Peter")
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(error, Error::DuplicateName(_)));
+
+        // A row can be renamed to the name it already has.
+        let data = repository.update(DataId::from(1), "Paul").await.unwrap();
+        assert_eq!(data.name, "Paul");
+        assert_eq!(names(&repository), ["Paul", "Peter", "Santiago", "Oliver"]);
+    }
+
+    #[actix_rt::test]
+    async fn update_many_cannot_share_a_name() {
+        let repository = repository();
+        let error = repository.update_many(&[1, 3], "New").await.err().unwrap();
+        assert!(matches!(error, Error::DuplicateName(_)));
+        let error = repository.update_many(&[1], "Oliver").await.err().unwrap();
+        assert!(matches!(error, Error::DuplicateName(_)));
+        assert_eq!(repository.update_many(&[1], "New").await.unwrap(), 1);
+        assert_eq!(names(&repository), ["New", "Peter", "Santiago", "Oliver"]);
+    }
+
+    #[actix_rt::test]
+    async fn insert_many_trims_names_or_inserts_none() {
+        let repository = repository();
+        let batch = ["Mary".to_owned(), " ".to_owned(), "John".to_owned()];
+        let error = repository.insert_many(&batch).await.err().unwrap();
+        assert!(matches!(error, Error::InvalidName(_)));
+        let batch = ["Mary".to_owned(), "x".repeat(MAX_NAME_LENGTH + 1)];
+        let error = repository.insert_many(&batch).await.err().unwrap();
+        assert!(matches!(error, Error::InvalidName(_)));
+        assert_eq!(names(&repository), ["Paul", "Peter", "Santiago", "Oliver"]);
+
+        let inserted = repository
+            .insert_many(&[" Mary ".to_owned(), "John\n".to_owned()])
+            .await
+            .unwrap();
+        assert_eq!(inserted.len(), 2);
+        assert_eq!(
+            names(&repository),
+            ["Paul", "Peter", "Santiago", "Oliver", "Mary", "John"]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn upsert_restores_the_row_with_that_name() {
+        let repository = repository();
+        repository.delete(DataId::from(3)).await.unwrap();
+        let data = repository.upsert("Santiago").await.unwrap();
+        assert_eq!(data.id, DataId::from(3));
+        assert!(data.deleted_at.is_none());
+    }
+}
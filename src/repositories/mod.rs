@@ -1,2 +1,7 @@
 pub mod error;
+pub mod memory;
 pub mod repo;
+pub mod slow_query;
+pub mod snapshot;
+#[cfg(feature = "sqlx")]
+pub mod sqlx;
@@ -1,70 +1,627 @@
-use crate::models::data::Data;
+use crate::models::data::{Data, DataId, DataStats};
+use crate::models::sort::{Sort, SortField, SortOrder};
 use crate::postgres::PostgresPool;
 use crate::repositories::error::Error;
+use crate::repositories::slow_query::SlowQueryLog;
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use coi::Inject;
-use mobc_postgres::tokio_postgres::NoTls;
+use futures::stream::{BoxStream, StreamExt};
+use mobc_postgres::mobc::{Connection, Manager};
+use mobc_postgres::tokio_postgres::types::ToSql;
+use mobc_postgres::tokio_postgres::{Client, Error as PostgresError, Row};
+use mobc_postgres::PgConnectionManager;
+use std::convert::TryFrom;
 use std::sync::Arc;
+use tracing::instrument;
 
+pub(crate) const SEED_SQL: &str = include_str!("../../xtask/src/sql/seed.sql");
+
+#[derive(Clone)]
 pub struct DbData {
-    id: i64,
-    name: String,
+    pub id: DataId,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+// Columns are looked up by name, so queries only need to select them, in any order.
+impl From<&Row> for DbData {
+    fn from(row: &Row) -> Self {
+        Self {
+            id: DataId::from(row.get::<_, i64>("id")),
+            name: row.get("name"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            deleted_at: row.get("deleted_at"),
+        }
+    }
+}
+
+// Longest name, in characters, that the repositories will store. Matches the DTO validation and
+// the `data_name_length` check on the column.
+pub const MAX_NAME_LENGTH: usize = 255;
+
+// Every repository checks names before writing them, so callers that don't go through the DTOs
+// can't store blank or oversized names either. Returns the name with surrounding whitespace trimmed.
+pub fn sanitize_name(name: &str) -> Result<&str, Error> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(Error::InvalidName("must not be blank"));
+    }
+    if name.chars().count() > MAX_NAME_LENGTH {
+        return Err(Error::InvalidName("must be at most 255 characters"));
+    }
+    Ok(name)
 }
 
-impl From<DbData> for Data {
-    fn from(db_data: DbData) -> Data {
-        Data {
+// Rows edited outside the API can break invariants it relies on, so they are rejected here
+// rather than handed to clients.
+impl TryFrom<DbData> for Data {
+    type Error = Error;
+
+    fn try_from(db_data: DbData) -> Result<Data, Error> {
+        if db_data.name.trim().is_empty() {
+            return Err(Error::InvalidRow(db_data.id, "name is empty"));
+        }
+        if db_data.updated_at < db_data.created_at {
+            return Err(Error::InvalidRow(
+                db_data.id,
+                "updated_at is before created_at",
+            ));
+        }
+        Ok(Data {
             id: db_data.id,
             name: db_data.name,
-        }
+            created_at: db_data.created_at,
+            updated_at: db_data.updated_at,
+            deleted_at: db_data.deleted_at,
+        })
     }
 }
 
 #[async_trait]
 pub trait IRepository: Inject {
-    async fn get(&self, id: i64) -> Result<DbData, Error>;
-    async fn get_all(&self) -> Result<Vec<DbData>, Error>;
+    async fn get(&self, id: DataId, include_deleted: bool) -> Result<DbData, Error>;
+    async fn exists(&self, id: DataId) -> Result<bool, Error>;
+    async fn get_all(
+        &self,
+        limit: i64,
+        offset: i64,
+        sort: Sort,
+        include_deleted: bool,
+    ) -> Result<Vec<DbData>, Error>;
+    async fn get_after(&self, after: i64, limit: i64) -> Result<Vec<DbData>, Error>;
+    async fn get_range(&self, from: i64, to: i64) -> Result<Vec<DbData>, Error>;
+    async fn get_recent(&self, limit: i64) -> Result<Vec<DbData>, Error>;
+    async fn get_random(&self) -> Result<DbData, Error>;
+    async fn get_many(&self, ids: &[i64]) -> Result<Vec<DbData>, Error>;
+    async fn stream_all(
+        &self,
+        sort: Sort,
+    ) -> Result<BoxStream<'static, Result<DbData, Error>>, Error>;
+    async fn insert(&self, name: &str) -> Result<DbData, Error>;
+    async fn insert_many(&self, names: &[String]) -> Result<Vec<DbData>, Error>;
+    async fn upsert(&self, name: &str) -> Result<DbData, Error>;
+    async fn update(&self, id: DataId, name: &str) -> Result<DbData, Error>;
+    async fn update_many(&self, ids: &[i64], name: &str) -> Result<u64, Error>;
+    async fn delete(&self, id: DataId) -> Result<u64, Error>;
+    async fn count(&self) -> Result<i64, Error>;
+    async fn stats(&self) -> Result<DataStats, Error>;
+    async fn search(&self, term: &str) -> Result<Vec<DbData>, Error>;
+    async fn ping(&self) -> Result<(), Error>;
+    async fn reseed(&self) -> Result<i64, Error>;
 }
 
-#[derive(Inject)]
-#[coi(provides pub dyn IRepository with Repository::new(pool))]
-struct Repository {
-    #[coi(inject)]
-    pool: Arc<PostgresPool<NoTls>>,
+// A connection with `statement_timeout` lifted, for a stream that runs for as long as its consumer
+// keeps reading. Dropping it restores the timeout before the connection goes back to the pool.
+struct UnlimitedConnection<T>(Option<Connection<PgConnectionManager<T>>>)
+where
+    PgConnectionManager<T>: Manager<Connection = Client, Error = PostgresError>;
+
+impl<T> UnlimitedConnection<T>
+where
+    PgConnectionManager<T>: Manager<Connection = Client, Error = PostgresError>,
+{
+    async fn new(client: Connection<PgConnectionManager<T>>) -> Result<Self, Error> {
+        client.batch_execute("SET statement_timeout = 0").await?;
+        Ok(Self(Some(client)))
+    }
+
+    fn client(&self) -> &Client {
+        self.0.as_ref().expect("only taken when dropped")
+    }
+}
+
+impl<T> Drop for UnlimitedConnection<T>
+where
+    PgConnectionManager<T>: Manager<Connection = Client, Error = PostgresError>,
+{
+    fn drop(&mut self) {
+        if let Some(client) = self.0.take() {
+            actix_rt::spawn(async move {
+                if let Err(e) = client.batch_execute("RESET statement_timeout").await {
+                    tracing::warn!("Failed to restore statement_timeout: {}", e);
+                }
+            });
+        }
+    }
 }
 
+// Mutations and health checks go through the writer pool (`pool`), queries through the reader
+// pool (`read_pool`), which may point at a replica.
+#[derive(Inject)]
+#[coi(provides pub dyn IRepository with Repository::<T>::new(pool, read_pool, slow_queries))]
+struct Repository<T>(
+    #[coi(inject = "pool")] Arc<PostgresPool<T>>,
+    #[coi(inject = "read_pool")] Arc<PostgresPool<T>>,
+    #[coi(inject = "slow_queries")] Arc<SlowQueryLog>,
+)
+where
+    PgConnectionManager<T>: Manager<Connection = Client, Error = PostgresError>;
+
 #[async_trait]
-impl IRepository for Repository {
-    async fn get(&self, id: i64) -> Result<DbData, Error> {
-        let client = self.pool.get().await?;
+impl<T> IRepository for Repository<T>
+where
+    PgConnectionManager<T>: Manager<Connection = Client, Error = PostgresError>,
+    T: Send + Sync + 'static,
+{
+    #[instrument(skip(self))]
+    async fn get(&self, id: DataId, include_deleted: bool) -> Result<DbData, Error> {
+        let client = self.1.get().await?;
+        let statement = client
+            .prepare(
+                "SELECT id, name, created_at, updated_at, deleted_at FROM data \
+                 WHERE id=$1::BIGINT AND ($2 OR deleted_at IS NULL)",
+            )
+            .await?;
+        let row = self
+            .2
+            .time(
+                "get",
+                client.query_opt(&statement, &[&i64::from(id), &include_deleted]),
+            )
+            .await?
+            .ok_or(Error::NotFound)?;
+        Ok(DbData::from(&row))
+    }
+
+    #[instrument(skip(self))]
+    async fn exists(&self, id: DataId) -> Result<bool, Error> {
+        let client = self.1.get().await?;
+        // Answered from the primary key index without reading the row.
+        let statement = client
+            .prepare("SELECT EXISTS(SELECT 1 FROM data WHERE id=$1::BIGINT AND deleted_at IS NULL)")
+            .await?;
+        let row = self
+            .2
+            .time("exists", client.query_one(&statement, &[&i64::from(id)]))
+            .await?;
+        Ok(row.get(0))
+    }
+
+    #[instrument(skip(self))]
+    async fn get_all(
+        &self,
+        limit: i64,
+        offset: i64,
+        sort: Sort,
+        include_deleted: bool,
+    ) -> Result<Vec<DbData>, Error> {
+        let client = self.1.get().await?;
+        let query = format!(
+            "SELECT id, name, created_at, updated_at, deleted_at FROM data \
+             WHERE $3 OR deleted_at IS NULL ORDER BY {} LIMIT $1 OFFSET $2",
+            order_by(sort)
+        );
+        let statement = client.prepare(&query).await?;
+        let rows = self
+            .2
+            .time(
+                "get_all",
+                client.query(&statement, &[&limit, &offset, &include_deleted]),
+            )
+            .await?;
+        let data = rows.iter().map(DbData::from).collect::<Vec<_>>();
+        Ok(data)
+    }
+
+    #[instrument(skip(self))]
+    async fn get_after(&self, after: i64, limit: i64) -> Result<Vec<DbData>, Error> {
+        let client = self.1.get().await?;
+        // Walks the primary key index, so every page is as cheap as the first no matter how deep.
+        let statement = client
+            .prepare(
+                "SELECT id, name, created_at, updated_at, deleted_at FROM data \
+                 WHERE id > $1 AND deleted_at IS NULL ORDER BY id LIMIT $2",
+            )
+            .await?;
+        let rows = self
+            .2
+            .time("get_after", client.query(&statement, &[&after, &limit]))
+            .await?;
+        let data = rows.iter().map(DbData::from).collect::<Vec<_>>();
+        Ok(data)
+    }
+
+    #[instrument(skip(self))]
+    async fn get_range(&self, from: i64, to: i64) -> Result<Vec<DbData>, Error> {
+        let client = self.1.get().await?;
         let statement = client
-            .prepare("SELECT id, name FROM data WHERE id=$1::BIGINT")
+            .prepare(
+                "SELECT id, name, created_at, updated_at, deleted_at FROM data \
+                 WHERE id BETWEEN $1 AND $2 AND deleted_at IS NULL ORDER BY id",
+            )
             .await?;
-        let row = client.query_one(&statement, &[&id]).await?;
-        let data = DbData {
-            id: row.get(0),
-            name: row.get(1),
-        };
+        let rows = self
+            .2
+            .time("get_range", client.query(&statement, &[&from, &to]))
+            .await?;
+        let data = rows.iter().map(DbData::from).collect::<Vec<_>>();
         Ok(data)
     }
 
-    async fn get_all(&self) -> Result<Vec<DbData>, Error> {
-        let client = self.pool.get().await?;
-        let statement = client.prepare("SELECT id, name FROM data LIMIT 50").await?;
-        let rows = client.query(&statement, &[]).await?;
-        let data = rows
-            .into_iter()
-            .map(|row| DbData {
-                id: row.get(0),
-                name: row.get(1),
-            })
-            .collect::<Vec<_>>();
+    #[instrument(skip(self))]
+    async fn get_recent(&self, limit: i64) -> Result<Vec<DbData>, Error> {
+        let client = self.1.get().await?;
+        select_recent(&client, &self.2, limit).await
+    }
+
+    #[instrument(skip(self))]
+    async fn get_random(&self) -> Result<DbData, Error> {
+        let client = self.1.get().await?;
+        // Sorts every live row, which is fine for demo-sized tables.
+        let statement = client
+            .prepare(
+                "SELECT id, name, created_at, updated_at, deleted_at FROM data \
+                 WHERE deleted_at IS NULL ORDER BY RANDOM() LIMIT 1",
+            )
+            .await?;
+        let row = self
+            .2
+            .time("get_random", client.query_opt(&statement, &[]))
+            .await?
+            .ok_or(Error::NotFound)?;
+        Ok(DbData::from(&row))
+    }
+
+    #[instrument(skip(self))]
+    async fn stream_all(
+        &self,
+        sort: Sort,
+    ) -> Result<BoxStream<'static, Result<DbData, Error>>, Error> {
+        let client = UnlimitedConnection::new(self.1.get().await?).await?;
+        let query = format!(
+            "SELECT id, name, created_at, updated_at, deleted_at FROM data \
+             WHERE deleted_at IS NULL ORDER BY {}",
+            order_by(sort)
+        );
+        let statement = client.client().prepare(&query).await?;
+        let params: [&(dyn ToSql + Sync); 0] = [];
+        // Only waiting for the first row is timed, the rest arrive as the caller consumes them.
+        let rows = self
+            .2
+            .time("stream_all", client.client().query_raw(&statement, params))
+            .await?;
+        // The closure owns the pooled connection so it isn't returned until the stream is dropped.
+        let data = rows.map(move |row| {
+            let _client = &client;
+            row.map(|row| DbData::from(&row)).map_err(Error::from)
+        });
+        Ok(data.boxed())
+    }
+
+    #[instrument(skip(self))]
+    async fn get_many(&self, ids: &[i64]) -> Result<Vec<DbData>, Error> {
+        let client = self.1.get().await?;
+        let statement = client
+            .prepare(
+                "SELECT id, name, created_at, updated_at, deleted_at FROM data \
+                 WHERE id = ANY($1) AND deleted_at IS NULL",
+            )
+            .await?;
+        let rows = self
+            .2
+            .time("get_many", client.query(&statement, &[&ids]))
+            .await?;
+        let data = rows.iter().map(DbData::from).collect::<Vec<_>>();
         Ok(data)
     }
+
+    #[instrument(skip(self))]
+    async fn insert(&self, name: &str) -> Result<DbData, Error> {
+        let name = sanitize_name(name)?;
+        let client = self.0.get().await?;
+        let statement = client
+            .prepare(
+                "INSERT INTO data (name) VALUES ($1) \
+                 RETURNING id, name, created_at, updated_at, deleted_at",
+            )
+            .await?;
+        let row = self
+            .2
+            .time("insert", client.query_one(&statement, &[&name]))
+            .await?;
+        Ok(DbData::from(&row))
+    }
+
+    #[instrument(skip(self))]
+    async fn insert_many(&self, names: &[String]) -> Result<Vec<DbData>, Error> {
+        let names = names
+            .iter()
+            .map(|name| sanitize_name(name))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut client = self.0.get().await?;
+        // Dropping the transaction without committing rolls it back, so an early return on any
+        // failed insert leaves the table untouched.
+        let transaction = client.transaction().await?;
+        let statement = transaction
+            .prepare(
+                "INSERT INTO data (name) VALUES ($1) \
+                 RETURNING id, name, created_at, updated_at, deleted_at",
+            )
+            .await?;
+        let mut data = Vec::with_capacity(names.len());
+        for name in names {
+            let row = self
+                .2
+                .time("insert_many", transaction.query_one(&statement, &[&name]))
+                .await?;
+            data.push(DbData::from(&row));
+        }
+        transaction.commit().await?;
+        Ok(data)
+    }
+
+    #[instrument(skip(self))]
+    async fn upsert(&self, name: &str) -> Result<DbData, Error> {
+        let name = sanitize_name(name)?;
+        let client = self.0.get().await?;
+        // A soft-deleted row with the same name is brought back rather than conflicting.
+        let statement = client
+            .prepare(
+                "INSERT INTO data (name) VALUES ($1) \
+                 ON CONFLICT (name) DO UPDATE SET updated_at=now(), deleted_at=NULL \
+                 RETURNING id, name, created_at, updated_at, deleted_at",
+            )
+            .await?;
+        let row = self
+            .2
+            .time("upsert", client.query_one(&statement, &[&name]))
+            .await?;
+        Ok(DbData::from(&row))
+    }
+
+    #[instrument(skip(self))]
+    async fn update(&self, id: DataId, name: &str) -> Result<DbData, Error> {
+        let name = sanitize_name(name)?;
+        let client = self.0.get().await?;
+        let statement = client
+            .prepare(
+                "UPDATE data SET name=$2, updated_at=now() WHERE id=$1 AND deleted_at IS NULL \
+                 RETURNING id, name, created_at, updated_at, deleted_at",
+            )
+            .await?;
+        let row = self
+            .2
+            .time(
+                "update",
+                client.query_opt(&statement, &[&i64::from(id), &name]),
+            )
+            .await?
+            .ok_or(Error::NotFound)?;
+        Ok(DbData::from(&row))
+    }
+
+    #[instrument(skip(self))]
+    async fn update_many(&self, ids: &[i64], name: &str) -> Result<u64, Error> {
+        let name = sanitize_name(name)?;
+        let client = self.0.get().await?;
+        let statement = client
+            .prepare(
+                "UPDATE data SET name=$2, updated_at=now() \
+                 WHERE id = ANY($1) AND deleted_at IS NULL",
+            )
+            .await?;
+        let updated = self
+            .2
+            .time("update_many", client.execute(&statement, &[&ids, &name]))
+            .await?;
+        Ok(updated)
+    }
+
+    #[instrument(skip(self))]
+    async fn delete(&self, id: DataId) -> Result<u64, Error> {
+        let client = self.0.get().await?;
+        // Soft delete: the row stays for history and is only hidden from reads.
+        let statement = client
+            .prepare("UPDATE data SET deleted_at=now() WHERE id=$1 AND deleted_at IS NULL")
+            .await?;
+        let affected = self
+            .2
+            .time("delete", client.execute(&statement, &[&i64::from(id)]))
+            .await?;
+        Ok(affected)
+    }
+
+    #[instrument(skip(self))]
+    async fn count(&self) -> Result<i64, Error> {
+        let client = self.1.get().await?;
+        let statement = client
+            .prepare("SELECT COUNT(*) FROM data WHERE deleted_at IS NULL")
+            .await?;
+        let row = self
+            .2
+            .time("count", client.query_one(&statement, &[]))
+            .await?;
+        Ok(row.get(0))
+    }
+
+    #[instrument(skip(self))]
+    async fn stats(&self) -> Result<DataStats, Error> {
+        let client = self.1.get().await?;
+        select_stats(&client, &self.2).await
+    }
+
+    #[instrument(skip(self))]
+    async fn search(&self, term: &str) -> Result<Vec<DbData>, Error> {
+        let client = self.1.get().await?;
+        // Served by the `data_name_trgm_idx` trigram index once the term is three or more
+        // characters, shorter terms have no trigrams to look up and scan the table.
+        let statement = client
+            .prepare(
+                "SELECT id, name, created_at, updated_at, deleted_at FROM data \
+                 WHERE name ILIKE $1 AND deleted_at IS NULL LIMIT 50",
+            )
+            .await?;
+        let pattern = format!("%{}%", escape_like(term));
+        let rows = self
+            .2
+            .time("search", client.query(&statement, &[&pattern]))
+            .await?;
+        let data = rows.iter().map(DbData::from).collect::<Vec<_>>();
+        Ok(data)
+    }
+
+    #[instrument(skip(self))]
+    async fn ping(&self) -> Result<(), Error> {
+        let client = self.0.get().await?;
+        client.simple_query("SELECT 1").await?;
+        Ok(())
+    }
+
+    // Replaces every row with the sample data from `cargo xtask seed`, ids starting from 1 again.
+    #[instrument(skip(self))]
+    async fn reseed(&self) -> Result<i64, Error> {
+        let mut client = self.0.get().await?;
+        let transaction = client.transaction().await?;
+        self.2
+            .time(
+                "reseed",
+                transaction.batch_execute(&format!("TRUNCATE data RESTART IDENTITY; {}", SEED_SQL)),
+            )
+            .await?;
+        let row = transaction
+            .query_one("SELECT COUNT(*) FROM data", &[])
+            .await?;
+        transaction.commit().await?;
+        Ok(row.get(0))
+    }
+}
+
+// Shared with `SnapshotRepository`, which runs them on the request's connection instead.
+pub(crate) async fn select_recent(
+    client: &Client,
+    slow_queries: &SlowQueryLog,
+    limit: i64,
+) -> Result<Vec<DbData>, Error> {
+    // Rows inserted in the same transaction share a `created_at`, so id breaks the tie.
+    let statement = client
+        .prepare(
+            "SELECT id, name, created_at, updated_at, deleted_at FROM data \
+             WHERE deleted_at IS NULL ORDER BY created_at DESC, id DESC LIMIT $1",
+        )
+        .await?;
+    let rows = slow_queries
+        .time("get_recent", client.query(&statement, &[&limit]))
+        .await?;
+    Ok(rows.iter().map(DbData::from).collect())
+}
+
+pub(crate) async fn select_stats(
+    client: &Client,
+    slow_queries: &SlowQueryLog,
+) -> Result<DataStats, Error> {
+    // `AVG` of integers is `numeric`, which has no `f64` mapping.
+    let statement = client
+        .prepare(
+            "SELECT COUNT(*), MIN(id), MAX(id), AVG(LENGTH(name))::FLOAT8 FROM data \
+             WHERE deleted_at IS NULL",
+        )
+        .await?;
+    let row = slow_queries
+        .time("stats", client.query_one(&statement, &[]))
+        .await?;
+    Ok(DataStats {
+        count: row.get(0),
+        min_id: row.get(1),
+        max_id: row.get(2),
+        avg_name_length: row.get(3),
+    })
+}
+
+// Only ever interpolates fixed column names and keywords, never user input.
+fn order_by(sort: Sort) -> &'static str {
+    match (sort.field, sort.order) {
+        (SortField::Id, SortOrder::Asc) => "id ASC",
+        (SortField::Id, SortOrder::Desc) => "id DESC",
+        (SortField::Name, SortOrder::Asc) => "name ASC, id ASC",
+        (SortField::Name, SortOrder::Desc) => "name DESC, id DESC",
+    }
 }
 
-impl Repository {
-    fn new(pool: Arc<PostgresPool<NoTls>>) -> Self {
-        Self { pool }
+pub(crate) fn escape_like(term: &str) -> String {
+    let mut escaped = String::with_capacity(term.len());
+    for c in term.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+impl<T> Repository<T>
+where
+    PgConnectionManager<T>: Manager<Connection = Client, Error = PostgresError>,
+{
+    fn new(
+        pool: Arc<PostgresPool<T>>,
+        read_pool: Arc<PostgresPool<T>>,
+        slow_queries: Arc<SlowQueryLog>,
+    ) -> Self {
+        Self(pool, read_pool, slow_queries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitize_name_trims_whitespace() {
+        assert_eq!(sanitize_name("  Paul \n").unwrap(), "Paul");
+        assert_eq!(sanitize_name("Mary Ann").unwrap(), "Mary Ann");
+    }
+
+    #[test]
+    fn sanitize_name_rejects_blank_names() {
+        for name in ["", " ", "\t\n"] {
+            let error = sanitize_name(name).err().unwrap();
+            assert!(matches!(error, Error::InvalidName("must not be blank")));
+        }
+    }
+
+    #[test]
+    fn sanitize_name_limits_length_in_characters() {
+        let longest = "x".repeat(MAX_NAME_LENGTH);
+        assert_eq!(sanitize_name(&longest).unwrap(), longest);
+        let error = sanitize_name(&"x".repeat(MAX_NAME_LENGTH + 1))
+            .err()
+            .unwrap();
+        assert!(matches!(error, Error::InvalidName(_)));
+
+        // Counted in characters like the column's `char_length` check, not in bytes.
+        let longest = "é".repeat(MAX_NAME_LENGTH);
+        assert_eq!(sanitize_name(&longest).unwrap(), longest);
+        let error = sanitize_name(&"é".repeat(MAX_NAME_LENGTH + 1))
+            .err()
+            .unwrap();
+        assert!(matches!(error, Error::InvalidName(_)));
+
+        // Surrounding whitespace doesn't count towards the limit.
+        let padded = format!(" {} ", "x".repeat(MAX_NAME_LENGTH));
+        assert_eq!(sanitize_name(&padded).unwrap().len(), MAX_NAME_LENGTH);
     }
 }
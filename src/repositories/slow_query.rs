@@ -0,0 +1,69 @@
+use crate::config::Config;
+use crate::repositories::error::Error;
+use coi::{Container, Inject, Provide};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[derive(Inject)]
+pub struct SlowQueryLog {
+    threshold: Duration,
+    timeout: Option<Duration>,
+}
+
+impl SlowQueryLog {
+    // Awaits `query`, failing with `QueryTimeout` if it runs past the configured timeout, and warns
+    // if it took at least the configured threshold. Postgres cancels the statement at the same
+    // timeout through `statement_timeout`, so its connection is free again for the next checkout.
+    pub async fn time<T, E, F>(&self, name: &'static str, query: F) -> Result<T, Error>
+    where
+        F: Future<Output = Result<T, E>>,
+        Error: From<E>,
+    {
+        let start = Instant::now();
+        let output = match self.timeout {
+            Some(timeout) => match actix_rt::time::timeout(timeout, query).await {
+                Ok(output) => output,
+                Err(_) => {
+                    tracing::warn!(
+                        query = name,
+                        timeout_ms = timeout.as_millis() as u64,
+                        "Query timed out"
+                    );
+                    return Err(Error::QueryTimeout);
+                }
+            },
+            None => query.await,
+        };
+        let elapsed = start.elapsed();
+        if elapsed >= self.threshold {
+            tracing::warn!(
+                query = name,
+                elapsed_ms = elapsed.as_millis() as u64,
+                threshold_ms = self.threshold.as_millis() as u64,
+                "Slow query"
+            );
+        }
+        output.map_err(Into::into)
+    }
+}
+
+// Uses `SLOW_QUERY_MS` and `QUERY_TIMEOUT_MS`.
+pub struct SlowQueryLogProvider;
+
+impl Provide for SlowQueryLogProvider {
+    type Output = SlowQueryLog;
+
+    fn provide(&self, container: &Container) -> coi::Result<Arc<SlowQueryLog>> {
+        let config = container.resolve::<Config>("config")?;
+        Ok(Arc::new(SlowQueryLog {
+            threshold: config.slow_query_threshold,
+            timeout: config.query_timeout,
+        }))
+    }
+
+    #[cfg(feature = "debug")]
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["config"]
+    }
+}
@@ -0,0 +1,180 @@
+use crate::models::data::DataStats;
+use crate::postgres::PostgresPool;
+use crate::repositories::error::Error;
+use crate::repositories::repo::{select_recent, select_stats, DbData, IRepository};
+use crate::repositories::slow_query::SlowQueryLog;
+use async_trait::async_trait;
+use coi::{Container, Inject, Provide};
+use futures::lock::{MappedMutexGuard, Mutex, MutexGuard};
+use mobc_postgres::mobc::{Connection, Manager};
+use mobc_postgres::tokio_postgres::{Client, Error as PostgresError};
+use mobc_postgres::PgConnectionManager;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use tracing::instrument;
+
+// Queries that should all see the same data within a request.
+#[async_trait]
+pub trait ISnapshotRepository: Inject {
+    async fn get_recent(&self, limit: i64) -> Result<Vec<DbData>, Error>;
+    async fn stats(&self) -> Result<DataStats, Error>;
+}
+
+// One reader connection per request, registered as scoped so every component resolved for the
+// request shares it. The connection is checked out on first use and holds a read-only
+// `REPEATABLE READ` transaction, so later queries see the same snapshot as the first one.
+pub struct RequestConnection<T>
+where
+    PgConnectionManager<T>: Manager<Connection = Client, Error = PostgresError>,
+{
+    read_pool: Arc<PostgresPool<T>>,
+    connection: Mutex<Option<Connection<PgConnectionManager<T>>>>,
+}
+
+impl<T> Inject for RequestConnection<T>
+where
+    PgConnectionManager<T>: Manager<Connection = Client, Error = PostgresError>,
+    T: Send + Sync + 'static,
+{
+}
+
+type ClientGuard<'a, T> = MappedMutexGuard<'a, Option<Connection<PgConnectionManager<T>>>, Client>;
+
+impl<T> RequestConnection<T>
+where
+    PgConnectionManager<T>: Manager<Connection = Client, Error = PostgresError>,
+{
+    fn new(read_pool: Arc<PostgresPool<T>>) -> Self {
+        Self {
+            read_pool,
+            connection: Mutex::new(None),
+        }
+    }
+
+    // Queries from the same request run one at a time, since they share a connection.
+    pub async fn client(&self) -> Result<ClientGuard<'_, T>, Error> {
+        let mut connection = self.connection.lock().await;
+        if connection.is_none() {
+            let client = self.read_pool.get().await?;
+            client
+                .batch_execute("BEGIN ISOLATION LEVEL REPEATABLE READ READ ONLY")
+                .await?;
+            *connection = Some(client);
+        }
+        Ok(MutexGuard::map(connection, |connection| {
+            &mut **connection.as_mut().expect("checked out above")
+        }))
+    }
+}
+
+impl<T> Drop for RequestConnection<T>
+where
+    PgConnectionManager<T>: Manager<Connection = Client, Error = PostgresError>,
+{
+    fn drop(&mut self) {
+        // Ending the transaction takes a round trip, so it runs after the request instead of
+        // blocking the drop. The connection goes back to the pool once it has finished.
+        if let Some(client) = self.connection.get_mut().take() {
+            actix_rt::spawn(async move {
+                if let Err(e) = client.batch_execute("ROLLBACK").await {
+                    tracing::warn!("Failed to end request transaction: {}", e);
+                }
+            });
+        }
+    }
+}
+
+pub struct RequestConnectionProvider<T>(PhantomData<fn() -> T>);
+
+impl<T> RequestConnectionProvider<T> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T> Default for RequestConnectionProvider<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Provide for RequestConnectionProvider<T>
+where
+    PgConnectionManager<T>: Manager<Connection = Client, Error = PostgresError>,
+    T: Send + Sync + 'static,
+{
+    type Output = RequestConnection<T>;
+
+    fn provide(&self, container: &Container) -> coi::Result<Arc<RequestConnection<T>>> {
+        let read_pool = container.resolve::<PostgresPool<T>>("read_pool")?;
+        Ok(Arc::new(RequestConnection::new(read_pool)))
+    }
+
+    #[cfg(feature = "debug")]
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["read_pool"]
+    }
+}
+
+#[derive(Inject)]
+#[coi(provides pub dyn ISnapshotRepository with SnapshotRepository::<T>::new(request_connection, slow_queries))]
+struct SnapshotRepository<T>(
+    #[coi(inject = "request_connection")] Arc<RequestConnection<T>>,
+    #[coi(inject = "slow_queries")] Arc<SlowQueryLog>,
+)
+where
+    PgConnectionManager<T>: Manager<Connection = Client, Error = PostgresError>;
+
+#[async_trait]
+impl<T> ISnapshotRepository for SnapshotRepository<T>
+where
+    PgConnectionManager<T>: Manager<Connection = Client, Error = PostgresError>,
+    T: Send + Sync + 'static,
+{
+    #[instrument(skip(self))]
+    async fn get_recent(&self, limit: i64) -> Result<Vec<DbData>, Error> {
+        let client = self.0.client().await?;
+        select_recent(&client, &self.1, limit).await
+    }
+
+    #[instrument(skip(self))]
+    async fn stats(&self) -> Result<DataStats, Error> {
+        let client = self.0.client().await?;
+        select_stats(&client, &self.1).await
+    }
+}
+
+impl<T> SnapshotRepository<T>
+where
+    PgConnectionManager<T>: Manager<Connection = Client, Error = PostgresError>,
+{
+    fn new(request_connection: Arc<RequestConnection<T>>, slow_queries: Arc<SlowQueryLog>) -> Self {
+        Self(request_connection, slow_queries)
+    }
+}
+
+// Without a database there is no snapshot to share, so each query goes straight to `repository`
+// and sees whatever the store holds at that moment.
+#[derive(Inject)]
+#[coi(provides pub dyn ISnapshotRepository with UnsharedSnapshotRepository::new(repository))]
+struct UnsharedSnapshotRepository {
+    #[coi(inject)]
+    repository: Arc<dyn IRepository>,
+}
+
+#[async_trait]
+impl ISnapshotRepository for UnsharedSnapshotRepository {
+    async fn get_recent(&self, limit: i64) -> Result<Vec<DbData>, Error> {
+        self.repository.get_recent(limit).await
+    }
+
+    async fn stats(&self) -> Result<DataStats, Error> {
+        self.repository.stats().await
+    }
+}
+
+impl UnsharedSnapshotRepository {
+    fn new(repository: Arc<dyn IRepository>) -> Self {
+        Self { repository }
+    }
+}
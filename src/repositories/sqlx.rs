@@ -0,0 +1,415 @@
+use crate::config::PoolConfig;
+use crate::models::data::{DataId, DataStats};
+use crate::models::sort::{Sort, SortField, SortOrder};
+use crate::repositories::error::Error;
+use crate::repositories::repo::{escape_like, sanitize_name, DbData, IRepository, SEED_SQL};
+use crate::repositories::slow_query::SlowQueryLog;
+use ::sqlx::postgres::{PgConnectOptions, PgPool, PgPoolOptions};
+use ::sqlx::Executor;
+use async_trait::async_trait;
+use coi::{Container, Inject, Provide};
+use futures::channel::mpsc;
+use futures::stream::{BoxStream, StreamExt};
+use futures::SinkExt;
+use mobc_postgres::tokio_postgres::config::Host;
+use mobc_postgres::tokio_postgres::Config as PgConfig;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::instrument;
+
+// Rows buffered between the query and a slow `stream_all` consumer.
+const STREAM_BUFFER: usize = 64;
+
+// Builds a lazily connecting sqlx pool for the same database and limits as `build_pool`. TLS isn't
+// enabled for sqlx, so `PGSSLMODE=require` is not supported with this repository.
+pub fn build_sqlx_pool(
+    config: &PgConfig,
+    pool_config: &PoolConfig,
+    query_timeout: Option<Duration>,
+) -> PgPool {
+    let mut options = PgConnectOptions::new();
+    if let Some(Host::Tcp(host)) = config.get_hosts().first() {
+        options = options.host(host);
+    }
+    if let Some(port) = config.get_ports().first() {
+        options = options.port(*port);
+    }
+    if let Some(user) = config.get_user() {
+        options = options.username(user);
+    }
+    if let Some(password) = config.get_password() {
+        options = options.password(&String::from_utf8_lossy(password));
+    }
+    if let Some(dbname) = config.get_dbname() {
+        options = options.database(dbname);
+    }
+    // Like `Config` does for tokio-postgres, so timed out statements are cancelled by Postgres.
+    if let Some(timeout) = query_timeout {
+        options = options.options([("statement_timeout", timeout.as_millis().to_string())]);
+    }
+    PgPoolOptions::new()
+        .max_connections(pool_config.max_open as u32)
+        .min_connections(pool_config.max_idle as u32)
+        .acquire_timeout(pool_config.timeout)
+        .max_lifetime(pool_config.max_lifetime)
+        .idle_timeout(pool_config.idle_timeout)
+        .connect_lazy_with(options)
+}
+
+// The same queries as `Repository`, but checked against the schema at compile time. Building with
+// the `sqlx` feature needs `DATABASE_URL` pointing at a migrated database, or `SQLX_OFFLINE=true`
+// to use the query data committed in `.sqlx`.
+#[derive(Inject)]
+struct SqlxRepository(PgPool, PgPool, Arc<SlowQueryLog>);
+
+#[async_trait]
+impl IRepository for SqlxRepository {
+    #[instrument(skip(self))]
+    async fn get(&self, id: DataId, include_deleted: bool) -> Result<DbData, Error> {
+        let query = ::sqlx::query_as!(
+            DbData,
+            "SELECT id, name, created_at, updated_at, deleted_at FROM data \
+             WHERE id = $1 AND ($2 OR deleted_at IS NULL)",
+            i64::from(id),
+            include_deleted
+        );
+        self.2
+            .time("get", query.fetch_optional(&self.1))
+            .await?
+            .ok_or(Error::NotFound)
+    }
+
+    #[instrument(skip(self))]
+    async fn exists(&self, id: DataId) -> Result<bool, Error> {
+        let query = ::sqlx::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1 FROM data WHERE id = $1 AND deleted_at IS NULL) AS "exists!""#,
+            i64::from(id)
+        );
+        let exists = self.2.time("exists", query.fetch_one(&self.1)).await?;
+        Ok(exists)
+    }
+
+    #[instrument(skip(self))]
+    async fn get_all(
+        &self,
+        limit: i64,
+        offset: i64,
+        sort: Sort,
+        include_deleted: bool,
+    ) -> Result<Vec<DbData>, Error> {
+        // The macros need a fixed statement, so the sort is chosen by parameters instead of
+        // interpolating an ORDER BY clause.
+        let by_name = matches!(sort.field, SortField::Name);
+        let desc = matches!(sort.order, SortOrder::Desc);
+        let query = ::sqlx::query_as!(
+            DbData,
+            "SELECT id, name, created_at, updated_at, deleted_at FROM data \
+             WHERE $3 OR deleted_at IS NULL \
+             ORDER BY \
+                CASE WHEN $4 AND NOT $5 THEN name END ASC, \
+                CASE WHEN $4 AND $5 THEN name END DESC, \
+                CASE WHEN NOT $5 THEN id END ASC, \
+                CASE WHEN $5 THEN id END DESC \
+             LIMIT $1 OFFSET $2",
+            limit,
+            offset,
+            include_deleted,
+            by_name,
+            desc
+        );
+        let data = self.2.time("get_all", query.fetch_all(&self.1)).await?;
+        Ok(data)
+    }
+
+    #[instrument(skip(self))]
+    async fn get_after(&self, after: i64, limit: i64) -> Result<Vec<DbData>, Error> {
+        let query = ::sqlx::query_as!(
+            DbData,
+            "SELECT id, name, created_at, updated_at, deleted_at FROM data \
+             WHERE id > $1 AND deleted_at IS NULL ORDER BY id LIMIT $2",
+            after,
+            limit
+        );
+        let data = self.2.time("get_after", query.fetch_all(&self.1)).await?;
+        Ok(data)
+    }
+
+    #[instrument(skip(self))]
+    async fn get_range(&self, from: i64, to: i64) -> Result<Vec<DbData>, Error> {
+        let query = ::sqlx::query_as!(
+            DbData,
+            "SELECT id, name, created_at, updated_at, deleted_at FROM data \
+             WHERE id BETWEEN $1 AND $2 AND deleted_at IS NULL ORDER BY id",
+            from,
+            to
+        );
+        let data = self.2.time("get_range", query.fetch_all(&self.1)).await?;
+        Ok(data)
+    }
+
+    #[instrument(skip(self))]
+    async fn get_recent(&self, limit: i64) -> Result<Vec<DbData>, Error> {
+        let query = ::sqlx::query_as!(
+            DbData,
+            "SELECT id, name, created_at, updated_at, deleted_at FROM data \
+             WHERE deleted_at IS NULL ORDER BY created_at DESC, id DESC LIMIT $1",
+            limit
+        );
+        let data = self.2.time("get_recent", query.fetch_all(&self.1)).await?;
+        Ok(data)
+    }
+
+    #[instrument(skip(self))]
+    async fn get_random(&self) -> Result<DbData, Error> {
+        let query = ::sqlx::query_as!(
+            DbData,
+            "SELECT id, name, created_at, updated_at, deleted_at FROM data \
+             WHERE deleted_at IS NULL ORDER BY RANDOM() LIMIT 1"
+        );
+        self.2
+            .time("get_random", query.fetch_optional(&self.1))
+            .await?
+            .ok_or(Error::NotFound)
+    }
+
+    #[instrument(skip(self))]
+    async fn get_many(&self, ids: &[i64]) -> Result<Vec<DbData>, Error> {
+        let query = ::sqlx::query_as!(
+            DbData,
+            "SELECT id, name, created_at, updated_at, deleted_at FROM data \
+             WHERE id = ANY($1) AND deleted_at IS NULL",
+            ids
+        );
+        let data = self.2.time("get_many", query.fetch_all(&self.1)).await?;
+        Ok(data)
+    }
+
+    #[instrument(skip(self))]
+    async fn stream_all(
+        &self,
+        sort: Sort,
+    ) -> Result<BoxStream<'static, Result<DbData, Error>>, Error> {
+        let by_name = matches!(sort.field, SortField::Name);
+        let desc = matches!(sort.order, SortOrder::Desc);
+        let pool = self.1.clone();
+        // sqlx streams borrow the pool, so rows are forwarded from a task that owns a handle to
+        // it. The bounded channel stops the query from running ahead of the consumer.
+        let (mut sender, receiver) = mpsc::channel(STREAM_BUFFER);
+        actix_rt::spawn(async move {
+            // The stream runs for as long as its consumer keeps reading, so `statement_timeout` is
+            // lifted for it and restored before the connection goes back to the pool.
+            let mut connection = match pool.acquire().await {
+                Ok(connection) => connection,
+                Err(e) => {
+                    let _ = sender.send(Err(Error::from(e))).await;
+                    return;
+                }
+            };
+            if let Err(e) = connection.execute("SET statement_timeout = 0").await {
+                let _ = sender.send(Err(Error::from(e))).await;
+                return;
+            }
+            let mut rows = ::sqlx::query_as!(
+                DbData,
+                "SELECT id, name, created_at, updated_at, deleted_at FROM data \
+                 WHERE deleted_at IS NULL \
+                 ORDER BY \
+                    CASE WHEN $1 AND NOT $2 THEN name END ASC, \
+                    CASE WHEN $1 AND $2 THEN name END DESC, \
+                    CASE WHEN NOT $2 THEN id END ASC, \
+                    CASE WHEN $2 THEN id END DESC",
+                by_name,
+                desc
+            )
+            .fetch(&mut *connection);
+            while let Some(row) = rows.next().await {
+                if sender.send(row.map_err(Error::from)).await.is_err() {
+                    break;
+                }
+            }
+            drop(rows);
+            if let Err(e) = connection.execute("RESET statement_timeout").await {
+                tracing::warn!("Failed to restore statement_timeout: {}", e);
+                connection.close_on_drop();
+            }
+        });
+        Ok(receiver.boxed())
+    }
+
+    #[instrument(skip(self))]
+    async fn insert(&self, name: &str) -> Result<DbData, Error> {
+        let name = sanitize_name(name)?;
+        let query = ::sqlx::query_as!(
+            DbData,
+            "INSERT INTO data (name) VALUES ($1) \
+             RETURNING id, name, created_at, updated_at, deleted_at",
+            name
+        );
+        let data = self.2.time("insert", query.fetch_one(&self.0)).await?;
+        Ok(data)
+    }
+
+    #[instrument(skip(self))]
+    async fn insert_many(&self, names: &[String]) -> Result<Vec<DbData>, Error> {
+        let names = names
+            .iter()
+            .map(|name| sanitize_name(name))
+            .collect::<Result<Vec<_>, _>>()?;
+        // Dropping the transaction without committing rolls it back.
+        let mut transaction = self.0.begin().await?;
+        let mut data = Vec::with_capacity(names.len());
+        for name in names {
+            let query = ::sqlx::query_as!(
+                DbData,
+                "INSERT INTO data (name) VALUES ($1) \
+                 RETURNING id, name, created_at, updated_at, deleted_at",
+                name
+            );
+            data.push(
+                self.2
+                    .time("insert_many", query.fetch_one(&mut *transaction))
+                    .await?,
+            );
+        }
+        transaction.commit().await?;
+        Ok(data)
+    }
+
+    #[instrument(skip(self))]
+    async fn upsert(&self, name: &str) -> Result<DbData, Error> {
+        let name = sanitize_name(name)?;
+        let query = ::sqlx::query_as!(
+            DbData,
+            "INSERT INTO data (name) VALUES ($1) \
+             ON CONFLICT (name) DO UPDATE SET updated_at=now(), deleted_at=NULL \
+             RETURNING id, name, created_at, updated_at, deleted_at",
+            name
+        );
+        let data = self.2.time("upsert", query.fetch_one(&self.0)).await?;
+        Ok(data)
+    }
+
+    #[instrument(skip(self))]
+    async fn update(&self, id: DataId, name: &str) -> Result<DbData, Error> {
+        let name = sanitize_name(name)?;
+        let query = ::sqlx::query_as!(
+            DbData,
+            "UPDATE data SET name=$2, updated_at=now() WHERE id=$1 AND deleted_at IS NULL \
+             RETURNING id, name, created_at, updated_at, deleted_at",
+            i64::from(id),
+            name
+        );
+        self.2
+            .time("update", query.fetch_optional(&self.0))
+            .await?
+            .ok_or(Error::NotFound)
+    }
+
+    #[instrument(skip(self))]
+    async fn update_many(&self, ids: &[i64], name: &str) -> Result<u64, Error> {
+        let name = sanitize_name(name)?;
+        let query = ::sqlx::query!(
+            "UPDATE data SET name=$2, updated_at=now() WHERE id = ANY($1) AND deleted_at IS NULL",
+            ids,
+            name
+        );
+        let result = self.2.time("update_many", query.execute(&self.0)).await?;
+        Ok(result.rows_affected())
+    }
+
+    #[instrument(skip(self))]
+    async fn delete(&self, id: DataId) -> Result<u64, Error> {
+        let query = ::sqlx::query!(
+            "UPDATE data SET deleted_at=now() WHERE id=$1 AND deleted_at IS NULL",
+            i64::from(id)
+        );
+        let result = self.2.time("delete", query.execute(&self.0)).await?;
+        Ok(result.rows_affected())
+    }
+
+    #[instrument(skip(self))]
+    async fn count(&self) -> Result<i64, Error> {
+        let query = ::sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "count!" FROM data WHERE deleted_at IS NULL"#
+        );
+        let count = self.2.time("count", query.fetch_one(&self.1)).await?;
+        Ok(count)
+    }
+
+    #[instrument(skip(self))]
+    async fn stats(&self) -> Result<DataStats, Error> {
+        let query = ::sqlx::query_as!(
+            DataStats,
+            r#"SELECT COUNT(*) AS "count!", MIN(id) AS min_id, MAX(id) AS max_id,
+                   AVG(LENGTH(name))::FLOAT8 AS avg_name_length
+               FROM data WHERE deleted_at IS NULL"#
+        );
+        let stats = self.2.time("stats", query.fetch_one(&self.1)).await?;
+        Ok(stats)
+    }
+
+    #[instrument(skip(self))]
+    async fn search(&self, term: &str) -> Result<Vec<DbData>, Error> {
+        let pattern = format!("%{}%", escape_like(term));
+        let query = ::sqlx::query_as!(
+            DbData,
+            "SELECT id, name, created_at, updated_at, deleted_at FROM data \
+             WHERE name ILIKE $1 AND deleted_at IS NULL LIMIT 50",
+            pattern
+        );
+        let data = self.2.time("search", query.fetch_all(&self.1)).await?;
+        Ok(data)
+    }
+
+    #[instrument(skip(self))]
+    async fn ping(&self) -> Result<(), Error> {
+        ::sqlx::query!("SELECT 1 AS one").fetch_one(&self.0).await?;
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn reseed(&self) -> Result<i64, Error> {
+        let mut transaction = self.0.begin().await?;
+        let sql = format!("TRUNCATE data RESTART IDENTITY; {}", SEED_SQL);
+        // A plain string is sent as a simple query, which may hold several statements.
+        self.2
+            .time("reseed", transaction.execute(sql.as_str()))
+            .await?;
+        let count = ::sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM data"#)
+            .fetch_one(&mut *transaction)
+            .await?;
+        transaction.commit().await?;
+        Ok(count)
+    }
+}
+
+// Mutations go through `pool` and queries through `read_pool`, like `Repository`. The pools are
+// owned by the provider rather than registered, since nothing else uses them.
+pub struct SqlxRepositoryProvider {
+    pool: PgPool,
+    read_pool: PgPool,
+}
+
+impl SqlxRepositoryProvider {
+    pub fn new(pool: PgPool, read_pool: PgPool) -> Self {
+        Self { pool, read_pool }
+    }
+}
+
+impl Provide for SqlxRepositoryProvider {
+    type Output = dyn IRepository;
+
+    fn provide(&self, container: &Container) -> coi::Result<Arc<dyn IRepository>> {
+        let slow_queries = container.resolve::<SlowQueryLog>("slow_queries")?;
+        Ok(Arc::new(SqlxRepository(
+            self.pool.clone(),
+            self.read_pool.clone(),
+            slow_queries,
+        )))
+    }
+
+    #[cfg(feature = "debug")]
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["slow_queries"]
+    }
+}
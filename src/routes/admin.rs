@@ -0,0 +1,87 @@
+use crate::dtos::data::CountDto;
+use crate::middleware::auth::bearer_token;
+use crate::services::admin::{AdminToken, IAdminService};
+use crate::services::error::Error as ServiceError;
+use actix_web::web::{self, ServiceConfig};
+use actix_web::{Error, HttpRequest, HttpResponse, Responder};
+use coi_actix_web::inject;
+
+fn authorize(req: &HttpRequest, token: &AdminToken) -> Result<(), ServiceError> {
+    match bearer_token(req.headers()) {
+        Some(candidate) if token.matches(candidate) => Ok(()),
+        _ => Err(ServiceError::Unauthorized),
+    }
+}
+
+// Resets the table to the sample data, for demos.
+#[inject]
+async fn reseed(
+    req: HttpRequest,
+    #[inject] admin_token: Arc<AdminToken>,
+    #[inject] admin: Arc<dyn IAdminService>,
+) -> Result<impl Responder, Error> {
+    authorize(&req, &admin_token)?;
+    let count = admin.reseed().await?;
+    Ok(HttpResponse::Ok().json(CountDto { count }))
+}
+
+pub fn route_config(config: &mut ServiceConfig) {
+    config.service(web::scope("/admin").route("/reseed", web::post().to(reseed)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::header::AUTHORIZATION;
+    use actix_web::http::StatusCode;
+    use actix_web::test::{self, TestRequest};
+    use actix_web::App;
+    use async_trait::async_trait;
+    use coi::{container, Inject, Provide};
+
+    #[derive(Inject)]
+    #[coi(provides dyn IAdminService with FakeAdmin)]
+    struct FakeAdmin;
+
+    #[async_trait]
+    impl IAdminService for FakeAdmin {
+        async fn reseed(&self) -> Result<i64, ServiceError> {
+            Ok(4)
+        }
+    }
+
+    #[derive(Provide)]
+    #[coi(provides AdminToken with AdminToken::new(self.0.map(str::to_owned)))]
+    struct FixedAdminToken(Option<&'static str>);
+
+    async fn reseed_status(admin_token: Option<&'static str>, req: TestRequest) -> StatusCode {
+        let container = container! {
+            admin_token => FixedAdminToken(admin_token); singleton,
+            admin => FakeAdminProvider; scoped,
+        };
+        let app = test::init_service(App::new().app_data(container).configure(route_config)).await;
+        let req = req.uri("/admin/reseed").to_request();
+        test::call_service(&app, req).await.status()
+    }
+
+    fn with_token(token: &str) -> TestRequest {
+        TestRequest::post().insert_header((AUTHORIZATION, format!("Bearer {}", token)))
+    }
+
+    #[actix_rt::test]
+    async fn reseed_requires_the_admin_token() {
+        let status = reseed_status(Some("admin"), TestRequest::post()).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let status = reseed_status(Some("admin"), with_token("wrong")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let status = reseed_status(Some("admin"), with_token("admin")).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[actix_rt::test]
+    async fn reseed_is_disabled_without_an_admin_token() {
+        for req in [TestRequest::post(), with_token(""), with_token("admin")] {
+            assert_eq!(reseed_status(None, req).await, StatusCode::UNAUTHORIZED);
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use crate::dtos::error::ErrorDto;
+use actix_web::error::{ErrorInternalServerError, InternalError, JsonPayloadError};
+use actix_web::http::header::{self, Accept, Header};
+use actix_web::http::StatusCode;
+use actix_web::web::JsonConfig;
+use actix_web::{Error, HttpMessage, HttpRequest, HttpResponse, HttpResponseBuilder};
+use serde::Serialize;
+
+const JSON_LIMIT: usize = 256 * 1024;
+
+pub(crate) const JSON_PATCH: &str = "application/json-patch+json";
+
+const MSGPACK: &str = "application/msgpack";
+const X_MSGPACK: &str = "application/x-msgpack";
+
+fn wants_msgpack(req: &HttpRequest) -> bool {
+    let accept = match Accept::parse(req) {
+        Ok(accept) => accept,
+        Err(_) => return false,
+    };
+    // The highest ranked type we can produce wins; anything else (including `*/*`) gets JSON.
+    accept
+        .ranked()
+        .into_iter()
+        .find(|mime| {
+            matches!(mime.essence_str(), MSGPACK | X_MSGPACK | "application/json")
+                || mime.type_() == "*"
+                || (mime.type_() == "application" && mime.subtype() == "*")
+        })
+        .is_some_and(|mime| matches!(mime.essence_str(), MSGPACK | X_MSGPACK))
+}
+
+// Serializes `body` as MessagePack when the client asks for it, and as JSON otherwise.
+pub(crate) fn negotiate<T: Serialize>(
+    req: &HttpRequest,
+    mut builder: HttpResponseBuilder,
+    body: &T,
+) -> Result<HttpResponse, Error> {
+    builder.insert_header((header::VARY, "Accept"));
+    if wants_msgpack(req) {
+        let bytes = rmp_serde::to_vec_named(body).map_err(ErrorInternalServerError)?;
+        Ok(builder.content_type(MSGPACK).body(bytes))
+    } else {
+        Ok(builder.json(body))
+    }
+}
+
+// Caps JSON bodies at 256KB and reports body errors in the same shape as the service errors.
+pub fn json_config() -> JsonConfig {
+    JsonConfig::default()
+        .limit(JSON_LIMIT)
+        .error_handler(json_error)
+}
+
+fn json_error(err: JsonPayloadError, _req: &HttpRequest) -> Error {
+    let (status, code) = match err {
+        JsonPayloadError::Overflow { .. } | JsonPayloadError::OverflowKnownLength { .. } => {
+            (StatusCode::PAYLOAD_TOO_LARGE, "PAYLOAD_TOO_LARGE")
+        }
+        JsonPayloadError::ContentType => {
+            (StatusCode::UNSUPPORTED_MEDIA_TYPE, "UNSUPPORTED_MEDIA_TYPE")
+        }
+        _ => (StatusCode::BAD_REQUEST, "INVALID_JSON"),
+    };
+    let response = HttpResponse::build(status).json(ErrorDto {
+        error: err.to_string(),
+        code,
+        errors: None,
+        request_id: None,
+    });
+    InternalError::from_response(err, response).into()
+}
+
+// The JSON extractor accepts any `+json` type, so routes that need a specific one check it here.
+// Rejections match the extractor's own `415`.
+pub(crate) fn require_content_type(req: &HttpRequest, content_type: &str) -> Result<(), Error> {
+    if req.content_type().eq_ignore_ascii_case(content_type) {
+        return Ok(());
+    }
+    let response = HttpResponse::UnsupportedMediaType().json(ErrorDto {
+        error: format!("Content type must be `{}`", content_type),
+        code: "UNSUPPORTED_MEDIA_TYPE",
+        errors: None,
+        request_id: None,
+    });
+    Err(InternalError::from_response("Unsupported content type", response).into())
+}
@@ -1,41 +1,1141 @@
-use crate::dtos::data::DataDto;
+use crate::dtos::data::{
+    BatchQueryDto, CountDto, CreateDataDto, DataDto, DataFields, DataPageDto, DataSummaryDto,
+    DeletedQueryDto, FieldsQueryDto, OverviewDto, RangeQueryDto, RecentQueryDto, SearchQueryDto,
+    StatsDto, UpdateDataDto, UpdateManyDto, UpdatedDto, ViewQueryDto, CSV_HEADER,
+};
+use crate::dtos::operations::OperationsDto;
+use crate::dtos::page::{CursorQueryDto, PageQueryDto, PagedDto};
+use crate::dtos::sort::SortQueryDto;
+use crate::middleware::auth::RequireWriteToken;
+use crate::models::data::{Data, DataId};
+use crate::routes::content::{negotiate, require_content_type, JSON_PATCH};
+use crate::routes::docs;
+use crate::services::aggregate::IAggregateService;
+use crate::services::error::{field_errors, Error as ServiceError, FieldErrors};
+use crate::services::events::DataEvents;
+use crate::services::idempotency::IdempotencyStore;
 use crate::services::service::IService;
-use actix_web::web::{self, ServiceConfig};
-use actix_web::{Error, HttpResponse, Responder};
+use crate::services::snapshot::ISnapshotService;
+use actix_rt::time::{interval_at, Instant};
+use actix_web::http::header::{
+    self, Allow, CacheControl, CacheDirective, ContentDisposition, ContentEncoding,
+    DispositionParam, DispositionType, ETag, EntityTag, Header, IfNoneMatch,
+};
+use actix_web::http::Method;
+use actix_web::web::{self, Bytes, ServiceConfig};
+use actix_web::{Error, HttpRequest, HttpResponse, Responder};
 use coi_actix_web::inject;
+use futures::future::try_join;
+use futures::stream::{self, StreamExt};
+use json_patch::{Patch, PatchErrorKind};
+use serde_json::Value;
+use std::sync::OnceLock;
+use std::time::Duration;
+use validator::Validate;
 
+const V1_PREFIX: &str = "/api/v1";
+const IDEMPOTENCY_KEY: &str = "Idempotency-Key";
+const IDEMPOTENT_REPLAYED: &str = "Idempotent-Replayed";
+const MAX_IDEMPOTENCY_KEY_LEN: usize = 255;
+// Comfortably below the idle timeouts of common proxies and load balancers.
+const EVENTS_HEARTBEAT: Duration = Duration::from_secs(15);
+
+// Weak because the same row can be served as JSON or MessagePack. Every change to a row moves
+// `updated_at`, except soft deletes, which set `deleted_at`, so the two timestamps identify a
+// version. Built from the values themselves so every build and instance agrees on it.
+fn etag(data: &Data, fields: DataFields) -> EntityTag {
+    let mut tag = format!("{}-{}", data.id, data.updated_at.timestamp_micros());
+    if let Some(deleted_at) = data.deleted_at {
+        tag.push_str(&format!("-{}", deleted_at.timestamp_micros()));
+    }
+    // A sparse body is a different representation of the row, so it can't share the full one's tag.
+    if fields != DataFields::ALL {
+        tag.push_str(&format!(";{}", fields));
+    }
+    EntityTag::new_weak(tag)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/data/{id}",
+    tag = "data",
+    params(
+        ("id" = i64, Path, description = "Id of the data row"),
+        DeletedQueryDto,
+        FieldsQueryDto,
+        ("Cache-Control" = Option<String>, Header,
+            description = "`no-cache` reads the row from the database instead of the cache"),
+    ),
+    responses(
+        (status = 200, description = "The data row, with only the requested `fields`",
+            body = DataDto,
+            headers(("ETag" = String,
+                description = "Weak validator for the row and the requested `fields`"))),
+        (status = 304, description = "The row matches If-None-Match"),
+        (status = 400, description = "Unknown field in `fields`", body = ErrorDto),
+        (status = 404, description = "No row with that id", body = ErrorDto),
+        (status = 500, description = "Repository failure", body = ErrorDto),
+        (status = 503, description = "No pooled connection became available", body = ErrorDto,
+            headers(("Retry-After" = u32, description = "Seconds to wait before retrying"))),
+    )
+)]
 #[inject]
 async fn get(
-    id: web::Path<i64>,
+    req: HttpRequest,
+    id: web::Path<DataId>,
+    deleted: web::Query<DeletedQueryDto>,
+    fields: web::Query<FieldsQueryDto>,
     #[inject] service: Arc<dyn IService>,
+    #[inject] uncached_service: Arc<dyn IService>,
 ) -> Result<impl Responder, Error> {
-    let data = service.get(*id).await?;
-    Ok(HttpResponse::Ok().json(DataDto::from(data)))
+    let fields = fields.resolve().map_err(ServiceError::Validation)?;
+    // Both keys provide `dyn IService`: `service` is the cached one and `uncached_service` the one
+    // it wraps, so a client asking for a fresh read skips the cache.
+    let no_cache = CacheControl::parse(&req)
+        .is_ok_and(|directives| directives.contains(&CacheDirective::NoCache));
+    let service = if no_cache { uncached_service } else { service };
+    let data = service.get(*id, deleted.include_deleted).await?;
+    let tag = etag(&data, fields);
+    let unchanged = match IfNoneMatch::parse(&req) {
+        Ok(IfNoneMatch::Any) => true,
+        Ok(IfNoneMatch::Items(tags)) => tags.iter().any(|t| t.weak_eq(&tag)),
+        Err(_) => false,
+    };
+    if unchanged {
+        return Ok(HttpResponse::NotModified()
+            .insert_header(ETag(tag))
+            .finish());
+    }
+    let mut response = HttpResponse::Ok();
+    response.insert_header(ETag(tag));
+    negotiate(&req, response, &DataDto::from(data).only(fields))
+}
+
+#[utoipa::path(
+    head,
+    path = "/api/v1/data/{id}",
+    tag = "data",
+    params(("id" = i64, Path, description = "Id of the data row")),
+    responses(
+        (status = 200, description = "A live row with that id exists"),
+        (status = 404, description = "No live row with that id"),
+        (status = 500, description = "Repository failure"),
+        (status = 503, description = "No pooled connection became available",
+            headers(("Retry-After" = u32, description = "Seconds to wait before retrying"))),
+    )
+)]
+#[inject]
+async fn exists(
+    id: web::Path<DataId>,
+    #[inject] service: Arc<dyn IService>,
+) -> Result<impl Responder, Error> {
+    // Checks the id without fetching the row, so there's no body or `ETag` to send.
+    if service.exists(*id).await? {
+        Ok(HttpResponse::Ok().finish())
+    } else {
+        Err(ServiceError::NotFound.into())
+    }
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/data/{id}/{id2}",
+    tag = "data",
+    params(
+        ("id" = i64, Path, description = "Id of the first data row"),
+        ("id2" = i64, Path, description = "Id of the second data row"),
+    ),
+    responses(
+        (status = 200, description = "Both data rows", body = [DataDto]),
+        (status = 404, description = "Either row is missing", body = ErrorDto),
+        (status = 500, description = "Repository failure", body = ErrorDto),
+        (status = 503, description = "No pooled connection became available", body = ErrorDto,
+            headers(("Retry-After" = u32, description = "Seconds to wait before retrying"))),
+    )
+)]
 #[inject]
 async fn use_two_deps(
-    path: web::Path<(i64, i64)>,
+    req: HttpRequest,
+    path: web::Path<(DataId, DataId)>,
     #[inject] service: Arc<dyn IService>,
     #[inject] service2: Arc<dyn IService>,
 ) -> Result<impl Responder, Error> {
-    let data = service.get(path.0).await?;
-    let data2 = service2.get(path.1).await?;
-    Ok(HttpResponse::Ok().json([DataDto::from(data), DataDto::from(data2)]))
+    let data = service.get(path.0, false).await?;
+    let data2 = service2.get(path.1, false).await?;
+    negotiate(
+        &req,
+        HttpResponse::Ok(),
+        &[DataDto::from(data), DataDto::from(data2)],
+    )
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/data",
+    tag = "data",
+    params(
+        PageQueryDto,
+        CursorQueryDto,
+        SortQueryDto,
+        DeletedQueryDto,
+        ViewQueryDto,
+        FieldsQueryDto,
+    ),
+    responses(
+        (status = 200, description = "A page of data rows, as `DataSummaryDto` with `view=summary`, \
+            wrapped in a `DataPageDto` with `after` or in a `PagedDataDto` with `paged=true`. \
+            Rows only have the requested `fields`",
+            body = [DataDto]),
+        (status = 400, description = "Invalid paging, cursor, sort, view or fields parameters",
+            body = ErrorDto),
+        (status = 500, description = "Repository failure", body = ErrorDto),
+        (status = 503, description = "No pooled connection became available", body = ErrorDto,
+            headers(("Retry-After" = u32, description = "Seconds to wait before retrying"))),
+    )
+)]
+// One extractor per query struct, so each is documented on its own through `IntoParams`.
+#[allow(clippy::too_many_arguments)]
+#[inject]
+async fn get_all(
+    req: HttpRequest,
+    page: web::Query<PageQueryDto>,
+    cursor: web::Query<CursorQueryDto>,
+    sort: web::Query<SortQueryDto>,
+    deleted: web::Query<DeletedQueryDto>,
+    view: web::Query<ViewQueryDto>,
+    fields: web::Query<FieldsQueryDto>,
+    #[inject] service: Arc<dyn IService>,
+) -> Result<impl Responder, Error> {
+    let summary = view
+        .is_summary()
+        .map_err(|e| ServiceError::Validation(e.to_owned()))?;
+    let has_fields = fields.is_set();
+    let fields = fields.resolve().map_err(ServiceError::Validation)?;
+    if summary && has_fields {
+        return Err(ServiceError::Validation(
+            "fields cannot be combined with view=summary".to_owned(),
+        )
+        .into());
+    }
+    let (limit, offset) = page
+        .resolve()
+        .map_err(|e| ServiceError::Validation(e.to_owned()))?;
+    if let Some(after) = cursor.after {
+        if page.has_offset()
+            || sort.is_set()
+            || deleted.include_deleted
+            || summary
+            || view.paged
+            || has_fields
+        {
+            return Err(ServiceError::Validation(
+                "after cannot be combined with offset, sort, order, include_deleted, view, paged \
+                 or fields"
+                    .to_owned(),
+            )
+            .into());
+        }
+        let data = service.get_after(after, limit).await?;
+        // A short page is the last one, so there is no cursor to continue from.
+        let next_after = match data.last() {
+            Some(last) if data.len() as i64 == limit => Some(last.id.into()),
+            _ => None,
+        };
+        let page = DataPageDto {
+            data: data.into_iter().map(DataDto::from).collect(),
+            next_after,
+        };
+        return negotiate(&req, HttpResponse::Ok(), &page);
+    }
+    let sort = sort
+        .resolve()
+        .map_err(|e| ServiceError::Validation(e.to_owned()))?;
+    if view.paged {
+        // `count` only counts live rows, so the total would be wrong for this page.
+        if deleted.include_deleted {
+            return Err(ServiceError::Validation(
+                "paged cannot be combined with include_deleted".to_owned(),
+            )
+            .into());
+        }
+        let (data, total) =
+            try_join(service.get_all(limit, offset, sort, false), service.count()).await?;
+        if summary {
+            let dtos = data.into_iter().map(DataSummaryDto::from).collect();
+            return negotiate(
+                &req,
+                HttpResponse::Ok(),
+                &PagedDto::new(dtos, total, limit, offset),
+            );
+        }
+        let dtos = data
+            .into_iter()
+            .map(|data| DataDto::from(data).only(fields))
+            .collect();
+        return negotiate(
+            &req,
+            HttpResponse::Ok(),
+            &PagedDto::new(dtos, total, limit, offset),
+        );
+    }
+    let data = service
+        .get_all(limit, offset, sort, deleted.include_deleted)
+        .await?;
+    if summary {
+        let dtos = data
+            .into_iter()
+            .map(DataSummaryDto::from)
+            .collect::<Vec<_>>();
+        return negotiate(&req, HttpResponse::Ok(), &dtos);
+    }
+    let dtos = data
+        .into_iter()
+        .map(|data| DataDto::from(data).only(fields))
+        .collect::<Vec<_>>();
+    negotiate(&req, HttpResponse::Ok(), &dtos)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/data/merged",
+    tag = "data",
+    params(PageQueryDto, SortQueryDto),
+    responses(
+        (status = 200, description = "A page of rows from both the configured and the in-memory \
+            repository, deduplicated by id", body = [DataDto]),
+        (status = 400, description = "Invalid paging or sort parameters", body = ErrorDto),
+        (status = 500, description = "Repository failure", body = ErrorDto),
+        (status = 503, description = "No pooled connection became available", body = ErrorDto,
+            headers(("Retry-After" = u32, description = "Seconds to wait before retrying"))),
+    )
+)]
+#[inject]
+async fn get_merged(
+    req: HttpRequest,
+    page: web::Query<PageQueryDto>,
+    sort: web::Query<SortQueryDto>,
+    #[inject] aggregate: Arc<dyn IAggregateService>,
+) -> Result<impl Responder, Error> {
+    let (limit, offset) = page
+        .resolve()
+        .map_err(|e| ServiceError::Validation(e.to_owned()))?;
+    let sort = sort
+        .resolve()
+        .map_err(|e| ServiceError::Validation(e.to_owned()))?;
+    let data = aggregate.get_all(limit, offset, sort).await?;
+    let dtos = data.into_iter().map(DataDto::from).collect::<Vec<_>>();
+    negotiate(&req, HttpResponse::Ok(), &dtos)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/data/range",
+    tag = "data",
+    params(RangeQueryDto),
+    responses(
+        (status = 200, description = "Every live row with an id between `from` and `to` inclusive, \
+            in id order", body = [DataDto]),
+        (status = 400, description = "Missing bounds or `from` is greater than `to`",
+            body = ErrorDto),
+        (status = 500, description = "Repository failure", body = ErrorDto),
+        (status = 503, description = "No pooled connection became available", body = ErrorDto,
+            headers(("Retry-After" = u32, description = "Seconds to wait before retrying"))),
+    )
+)]
+#[inject]
+async fn get_range(
+    req: HttpRequest,
+    range: web::Query<RangeQueryDto>,
+    #[inject] service: Arc<dyn IService>,
+) -> Result<impl Responder, Error> {
+    range
+        .check()
+        .map_err(|e| ServiceError::Validation(e.to_owned()))?;
+    let data = service.get_range(range.from, range.to).await?;
+    let dtos = data.into_iter().map(DataDto::from).collect::<Vec<_>>();
+    negotiate(&req, HttpResponse::Ok(), &dtos)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/data/recent",
+    tag = "data",
+    params(RecentQueryDto),
+    responses(
+        (status = 200, description = "The most recently created live rows, newest first",
+            body = [DataDto]),
+        (status = 400, description = "Negative limit", body = ErrorDto),
+        (status = 500, description = "Repository failure", body = ErrorDto),
+        (status = 503, description = "No pooled connection became available", body = ErrorDto,
+            headers(("Retry-After" = u32, description = "Seconds to wait before retrying"))),
+    )
+)]
+#[inject]
+async fn get_recent(
+    req: HttpRequest,
+    recent: web::Query<RecentQueryDto>,
+    #[inject] service: Arc<dyn IService>,
+) -> Result<impl Responder, Error> {
+    let limit = recent
+        .resolve()
+        .map_err(|e| ServiceError::Validation(e.to_owned()))?;
+    let data = service.get_recent(limit).await?;
+    let dtos = data.into_iter().map(DataDto::from).collect::<Vec<_>>();
+    negotiate(&req, HttpResponse::Ok(), &dtos)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/data/random",
+    tag = "data",
+    responses(
+        (status = 200, description = "A live row picked at random", body = DataDto),
+        (status = 404, description = "There are no live rows", body = ErrorDto),
+        (status = 500, description = "Repository failure", body = ErrorDto),
+        (status = 503, description = "No pooled connection became available", body = ErrorDto,
+            headers(("Retry-After" = u32, description = "Seconds to wait before retrying"))),
+    )
+)]
+#[inject]
+async fn get_random(
+    req: HttpRequest,
+    #[inject] service: Arc<dyn IService>,
+) -> Result<impl Responder, Error> {
+    let data = service.get_random().await?;
+    negotiate(&req, HttpResponse::Ok(), &DataDto::from(data))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/data/stream",
+    tag = "data",
+    params(SortQueryDto),
+    responses(
+        (status = 200, description = "Every live row as newline-delimited JSON", body = DataDto,
+            content_type = "application/x-ndjson"),
+        (status = 400, description = "Invalid sort parameters", body = ErrorDto),
+        (status = 500, description = "Repository failure", body = ErrorDto),
+    )
+)]
+#[inject]
+async fn stream(
+    sort: web::Query<SortQueryDto>,
+    #[inject] service: Arc<dyn IService>,
+) -> Result<impl Responder, Error> {
+    let sort = sort
+        .resolve()
+        .map_err(|e| ServiceError::Validation(e.to_owned()))?;
+    // Each row is serialized as it arrives, so the full result set is never held in memory.
+    let lines = service.stream_all(sort).await?.map(|data| {
+        let mut line = serde_json::to_vec(&DataDto::from(data?))?;
+        line.push(b'\n');
+        Ok::<_, Error>(Bytes::from(line))
+    });
+    Ok(HttpResponse::Ok()
+        .content_type("application/x-ndjson")
+        .streaming(lines))
+}
+
+// The spec is fixed at compile time, so it is only summarized for the first request.
+static OPERATIONS: OnceLock<OperationsDto> = OnceLock::new();
+
+#[utoipa::path(
+    options,
+    path = "/api/v1/data",
+    tag = "data",
+    responses(
+        (status = 200, description = "Every operation under `/api/v1/data` with its parameters, \
+            summarized from the OpenAPI spec", body = OperationsDto,
+            headers(("Allow" = String, description = "Methods served for `/api/v1/data`"))),
+    )
+)]
+async fn options() -> impl Responder {
+    let operations = OPERATIONS.get_or_init(|| OperationsDto::from(&docs::openapi()));
+    let path = format!("{}/data", V1_PREFIX);
+    let allow = operations
+        .methods(&path)
+        .filter_map(|method| Method::from_bytes(method.as_bytes()).ok())
+        .collect();
+    HttpResponse::Ok()
+        .insert_header(Allow(allow))
+        .json(operations)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/data/events",
+    tag = "data",
+    responses(
+        (status = 200, description = "Server-sent events that stay open, with a message for every \
+            inserted row and a `: heartbeat` comment every 15 seconds", body = DataDto,
+            content_type = "text/event-stream"),
+    )
+)]
+#[inject]
+async fn events(#[inject] events: Arc<DataEvents>) -> Result<impl Responder, Error> {
+    let messages = events.subscribe().map(|data| {
+        let id = data.id;
+        let json = serde_json::to_string(&DataDto::from(data))?;
+        Ok::<_, Error>(Bytes::from(format!("id: {}\ndata: {}\n\n", id, json)))
+    });
+    // Comments are ignored by clients, but keep proxies from closing a quiet stream.
+    let start = Instant::now() + EVENTS_HEARTBEAT;
+    let heartbeats = stream::unfold(interval_at(start, EVENTS_HEARTBEAT), |mut interval| async {
+        interval.tick().await;
+        Some((Ok(Bytes::from_static(b": heartbeat\n\n")), interval))
+    });
+    // Compression would hold events back until enough of them fill a block.
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(CacheControl(vec![CacheDirective::NoCache]))
+        .insert_header(ContentEncoding::Identity)
+        .streaming(stream::select(messages, heartbeats)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/data/export.csv",
+    tag = "data",
+    params(SortQueryDto),
+    responses(
+        (status = 200, description = "Every live row as CSV with a header row", body = String,
+            content_type = "text/csv",
+            headers(("Content-Disposition" = String, description = "Saves the body as `data.csv`"))),
+        (status = 400, description = "Invalid sort parameters", body = ErrorDto),
+        (status = 500, description = "Repository failure", body = ErrorDto),
+    )
+)]
+#[inject]
+async fn export_csv(
+    sort: web::Query<SortQueryDto>,
+    #[inject] service: Arc<dyn IService>,
+) -> Result<impl Responder, Error> {
+    let sort = sort
+        .resolve()
+        .map_err(|e| ServiceError::Validation(e.to_owned()))?;
+    // Streamed like `stream`, with the header row sent ahead of the first record.
+    let records = service.stream_all(sort).await?.map(|data| {
+        let record = DataDto::from(data?).to_csv_record();
+        Ok::<_, Error>(Bytes::from(record))
+    });
+    let header = futures::stream::once(async { Ok(Bytes::from_static(CSV_HEADER.as_bytes())) });
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename("data.csv".to_owned())],
+        })
+        .streaming(header.chain(records)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/data/batch",
+    tag = "data",
+    params(BatchQueryDto),
+    responses(
+        (status = 200, description = "The rows that exist out of the requested ids", body = [DataDto]),
+        (status = 400, description = "Invalid id list", body = ErrorDto),
+        (status = 500, description = "Repository failure", body = ErrorDto),
+        (status = 503, description = "No pooled connection became available", body = ErrorDto,
+            headers(("Retry-After" = u32, description = "Seconds to wait before retrying"))),
+    )
+)]
+#[inject]
+async fn get_many(
+    query: web::Query<BatchQueryDto>,
+    #[inject] service: Arc<dyn IService>,
+) -> Result<impl Responder, Error> {
+    let ids = query
+        .parse_ids()
+        .map_err(|e| ServiceError::Validation(e.to_owned()))?;
+    let data = service.get_many(&ids).await?;
+    Ok(HttpResponse::Ok().json(data.into_iter().map(DataDto::from).collect::<Vec<_>>()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/data/count",
+    tag = "data",
+    responses(
+        (status = 200, description = "Number of data rows", body = CountDto),
+        (status = 500, description = "Repository failure", body = ErrorDto),
+        (status = 503, description = "No pooled connection became available", body = ErrorDto,
+            headers(("Retry-After" = u32, description = "Seconds to wait before retrying"))),
+    )
+)]
+#[inject]
+async fn count(#[inject] service: Arc<dyn IService>) -> Result<impl Responder, Error> {
+    let count = service.count().await?;
+    Ok(HttpResponse::Ok().json(CountDto { count }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/data/overview",
+    tag = "data",
+    params(RecentQueryDto),
+    responses(
+        (status = 200, description = "Row statistics and the most recently created live rows, \
+            read from a single snapshot", body = OverviewDto),
+        (status = 400, description = "Negative limit", body = ErrorDto),
+        (status = 500, description = "Repository failure", body = ErrorDto),
+        (status = 503, description = "No pooled connection became available", body = ErrorDto,
+            headers(("Retry-After" = u32, description = "Seconds to wait before retrying"))),
+    )
+)]
+#[inject]
+async fn get_overview(
+    recent: web::Query<RecentQueryDto>,
+    #[inject] snapshot: Arc<dyn ISnapshotService>,
+) -> Result<impl Responder, Error> {
+    let limit = recent
+        .resolve()
+        .map_err(|e| ServiceError::Validation(e.to_owned()))?;
+    // Both calls run on the request's connection, inside the transaction the first one opens.
+    let stats = snapshot.stats().await?;
+    let recent = snapshot.get_recent(limit).await?;
+    Ok(HttpResponse::Ok().json(OverviewDto {
+        stats: StatsDto::from(stats),
+        recent: recent.into_iter().map(DataDto::from).collect(),
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/data/stats",
+    tag = "data",
+    responses(
+        (status = 200, description = "Summary of the live rows, from a single query", body = StatsDto),
+        (status = 500, description = "Repository failure", body = ErrorDto),
+        (status = 503, description = "No pooled connection became available", body = ErrorDto,
+            headers(("Retry-After" = u32, description = "Seconds to wait before retrying"))),
+    )
+)]
+#[inject]
+async fn stats(#[inject] service: Arc<dyn IService>) -> Result<impl Responder, Error> {
+    let stats = service.stats().await?;
+    Ok(HttpResponse::Ok().json(StatsDto::from(stats)))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/data/search",
+    tag = "data",
+    params(SearchQueryDto),
+    responses(
+        (status = 200, description = "Up to 50 rows whose name contains the term", body = [DataDto]),
+        (status = 400, description = "Empty search term", body = ErrorDto),
+        (status = 500, description = "Repository failure", body = ErrorDto),
+        (status = 503, description = "No pooled connection became available", body = ErrorDto,
+            headers(("Retry-After" = u32, description = "Seconds to wait before retrying"))),
+    )
+)]
 #[inject]
-async fn get_all(#[inject] service: Arc<dyn IService>) -> Result<impl Responder, Error> {
-    let data = service.get_all().await?;
+async fn search(
+    query: web::Query<SearchQueryDto>,
+    #[inject] service: Arc<dyn IService>,
+) -> Result<impl Responder, Error> {
+    if query.q.trim().is_empty() {
+        return Err(ServiceError::Validation("q must not be empty".to_owned()).into());
+    }
+    let data = service.search(&query.q).await?;
     Ok(HttpResponse::Ok().json(data.into_iter().map(DataDto::from).collect::<Vec<_>>()))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/v1/data",
+    tag = "data",
+    params(("Idempotency-Key" = Option<String>, Header,
+        description = "Retries with the same key return the original row without inserting")),
+    request_body = CreateDataDto,
+    responses(
+        (status = 201, description = "The created row", body = DataDto,
+            headers(
+                ("Location" = String, description = "Url of the created row"),
+                ("Idempotent-Replayed" = bool,
+                    description = "Present when an earlier request with the same key created the row"),
+            )),
+        (status = 401, description = "`WRITE_TOKEN` is set and the request doesn't carry it",
+            body = ErrorDto, headers(("WWW-Authenticate" = String, description = "`Bearer`"))),
+        (status = 400, description = "Invalid Idempotency-Key", body = ErrorDto),
+        (status = 422, description = "Invalid name", body = ErrorDto),
+        (status = 409, description = "Violates a unique or foreign key constraint, or the \
+            Idempotency-Key was already used with a different name", body = ErrorDto),
+        (status = 500, description = "Repository failure", body = ErrorDto),
+        (status = 503, description = "No pooled connection became available", body = ErrorDto,
+            headers(("Retry-After" = u32, description = "Seconds to wait before retrying"))),
+    )
+)]
+#[inject]
+async fn create(
+    req: HttpRequest,
+    body: web::Json<CreateDataDto>,
+    #[inject] service: Arc<dyn IService>,
+    #[inject] idempotency: Arc<IdempotencyStore>,
+) -> Result<impl Responder, Error> {
+    body.validate().map_err(ServiceError::from)?;
+    let key = match idempotency_key(&req)? {
+        Some(key) => key,
+        None => return Ok(created(service.insert(&body.name).await?, false)),
+    };
+
+    // Holding the key's lock across the insert makes a concurrent retry wait for this result.
+    let slot = idempotency.slot(key);
+    let mut slot = slot.lock().await;
+    if let Some((name, data)) = &*slot {
+        if *name != body.name {
+            let message = format!("{} was already used with a different name", IDEMPOTENCY_KEY);
+            return Err(ServiceError::Conflict(message).into());
+        }
+        return Ok(created(data.clone(), true));
+    }
+    // Failures aren't remembered, so the client can retry them with the same key.
+    let data = service.insert(&body.name).await?;
+    *slot = Some((body.into_inner().name, data.clone()));
+    Ok(created(data, false))
+}
+
+fn idempotency_key(req: &HttpRequest) -> Result<Option<&str>, ServiceError> {
+    let key = match req.headers().get(IDEMPOTENCY_KEY) {
+        Some(key) => key,
+        None => return Ok(None),
+    };
+    key.to_str()
+        .ok()
+        .filter(|key| !key.is_empty() && key.len() <= MAX_IDEMPOTENCY_KEY_LEN)
+        .map(Some)
+        .ok_or_else(|| {
+            ServiceError::Validation(format!(
+                "{} must be 1 to {} visible ASCII characters",
+                IDEMPOTENCY_KEY, MAX_IDEMPOTENCY_KEY_LEN
+            ))
+        })
+}
+
+fn created(data: Data, replayed: bool) -> HttpResponse {
+    let mut response = HttpResponse::Created();
+    response.insert_header((header::LOCATION, format!("{}/data/{}", V1_PREFIX, data.id)));
+    if replayed {
+        response.insert_header((IDEMPOTENT_REPLAYED, "true"));
+    }
+    response.json(DataDto::from(data))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/data/batch",
+    tag = "data",
+    request_body = [CreateDataDto],
+    responses(
+        (status = 201, description = "The created rows, in request order", body = [DataDto]),
+        (status = 401, description = "`WRITE_TOKEN` is set and the request doesn't carry it",
+            body = ErrorDto, headers(("WWW-Authenticate" = String, description = "`Bearer`"))),
+        (status = 400, description = "Empty batch", body = ErrorDto),
+        (status = 422, description = "Invalid name", body = ErrorDto),
+        (status = 409, description = "Violates a unique or foreign key constraint", body = ErrorDto),
+        (status = 500, description = "Repository failure, nothing was inserted", body = ErrorDto),
+        (status = 503, description = "No pooled connection became available", body = ErrorDto,
+            headers(("Retry-After" = u32, description = "Seconds to wait before retrying"))),
+    )
+)]
+#[inject]
+async fn create_many(
+    body: web::Json<Vec<CreateDataDto>>,
+    #[inject] service: Arc<dyn IService>,
+) -> Result<impl Responder, Error> {
+    if body.is_empty() {
+        return Err(ServiceError::Validation("batch must not be empty".to_owned()).into());
+    }
+    // Every item is checked so one response can point at all of the invalid ones.
+    let mut errors = FieldErrors::new();
+    for (index, dto) in body.iter().enumerate() {
+        if let Err(e) = dto.validate() {
+            field_errors(&format!("[{}]", index), &e, &mut errors);
+        }
+    }
+    if !errors.is_empty() {
+        return Err(ServiceError::Invalid(errors).into());
+    }
+    let names = body
+        .into_inner()
+        .into_iter()
+        .map(|dto| dto.name)
+        .collect::<Vec<_>>();
+    let data = service.insert_many(&names).await?;
+    Ok(HttpResponse::Created().json(data.into_iter().map(DataDto::from).collect::<Vec<_>>()))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/data/batch",
+    tag = "data",
+    request_body = UpdateManyDto,
+    responses(
+        (status = 200, description = "How many live rows were renamed, missing ids are skipped",
+            body = UpdatedDto),
+        (status = 401, description = "`WRITE_TOKEN` is set and the request doesn't carry it",
+            body = ErrorDto, headers(("WWW-Authenticate" = String, description = "`Bearer`"))),
+        (status = 422, description = "Empty id list or invalid name", body = ErrorDto),
+        (status = 409, description = "The name is taken, including by another row in the batch",
+            body = ErrorDto),
+        (status = 500, description = "Repository failure, nothing was updated", body = ErrorDto),
+        (status = 503, description = "No pooled connection became available", body = ErrorDto,
+            headers(("Retry-After" = u32, description = "Seconds to wait before retrying"))),
+    )
+)]
+#[inject]
+async fn update_many(
+    body: web::Json<UpdateManyDto>,
+    #[inject] service: Arc<dyn IService>,
+) -> Result<impl Responder, Error> {
+    body.validate().map_err(ServiceError::from)?;
+    let updated = service.update_many(&body.ids, &body.name).await?;
+    Ok(HttpResponse::Ok().json(UpdatedDto { updated }))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/data",
+    tag = "data",
+    request_body = CreateDataDto,
+    responses(
+        (status = 200, description = "The row with that name, created if missing", body = DataDto),
+        (status = 401, description = "`WRITE_TOKEN` is set and the request doesn't carry it",
+            body = ErrorDto, headers(("WWW-Authenticate" = String, description = "`Bearer`"))),
+        (status = 422, description = "Invalid name", body = ErrorDto),
+        (status = 500, description = "Repository failure", body = ErrorDto),
+        (status = 503, description = "No pooled connection became available", body = ErrorDto,
+            headers(("Retry-After" = u32, description = "Seconds to wait before retrying"))),
+    )
+)]
+#[inject]
+async fn upsert(
+    body: web::Json<CreateDataDto>,
+    #[inject] service: Arc<dyn IService>,
+) -> Result<impl Responder, Error> {
+    body.validate().map_err(ServiceError::from)?;
+    let data = service.upsert(&body.name).await?;
+    Ok(HttpResponse::Ok().json(DataDto::from(data)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/data/{id}",
+    tag = "data",
+    params(("id" = i64, Path, description = "Id of the data row")),
+    request_body = UpdateDataDto,
+    responses(
+        (status = 200, description = "The updated row", body = DataDto),
+        (status = 401, description = "`WRITE_TOKEN` is set and the request doesn't carry it",
+            body = ErrorDto, headers(("WWW-Authenticate" = String, description = "`Bearer`"))),
+        (status = 422, description = "Invalid name", body = ErrorDto),
+        (status = 404, description = "No row with that id", body = ErrorDto),
+        (status = 409, description = "Violates a unique or foreign key constraint", body = ErrorDto),
+        (status = 500, description = "Repository failure", body = ErrorDto),
+        (status = 503, description = "No pooled connection became available", body = ErrorDto,
+            headers(("Retry-After" = u32, description = "Seconds to wait before retrying"))),
+    )
+)]
+#[inject]
+async fn update(
+    id: web::Path<DataId>,
+    body: web::Json<UpdateDataDto>,
+    #[inject] service: Arc<dyn IService>,
+) -> Result<impl Responder, Error> {
+    body.validate().map_err(ServiceError::from)?;
+    let data = service.update(*id, &body.name).await?;
+    Ok(HttpResponse::Ok().json(DataDto::from(data)))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/data/{id}",
+    tag = "data",
+    params(("id" = i64, Path, description = "Id of the data row")),
+    request_body(content = Vec<Object>, content_type = "application/json-patch+json",
+        description = "RFC 6902 operations applied to the row as returned by `GET`. Only `name` \
+            may change"),
+    responses(
+        (status = 200, description = "The updated row", body = DataDto),
+        (status = 401, description = "`WRITE_TOKEN` is set and the request doesn't carry it",
+            body = ErrorDto, headers(("WWW-Authenticate" = String, description = "`Bearer`"))),
+        (status = 400, description = "Malformed patch, or it changes a field other than `name`",
+            body = ErrorDto),
+        (status = 404, description = "No row with that id", body = ErrorDto),
+        (status = 409, description = "A `test` operation failed, or the new name is taken",
+            body = ErrorDto),
+        (status = 415, description = "The body isn't `application/json-patch+json`",
+            body = ErrorDto),
+        (status = 422, description = "Invalid name", body = ErrorDto),
+        (status = 500, description = "Repository failure", body = ErrorDto),
+        (status = 503, description = "No pooled connection became available", body = ErrorDto,
+            headers(("Retry-After" = u32, description = "Seconds to wait before retrying"))),
+    )
+)]
+#[inject]
+async fn patch(
+    req: HttpRequest,
+    id: web::Path<DataId>,
+    body: web::Json<Patch>,
+    #[inject] service: Arc<dyn IService>,
+) -> Result<impl Responder, Error> {
+    require_content_type(&req, JSON_PATCH)?;
+    let current = DataDto::from(service.get(*id, false).await?);
+    let update = UpdateDataDto {
+        name: apply_patch(&current, &body)?,
+    };
+    update.validate().map_err(ServiceError::from)?;
+    let data = service.update(*id, &update.name).await?;
+    Ok(HttpResponse::Ok().json(DataDto::from(data)))
+}
+
+// Applies `patch` to the JSON form of `current` and returns the resulting name. Every other field
+// is read-only, so the patched document must otherwise be unchanged.
+fn apply_patch(current: &DataDto, patch: &Patch) -> Result<String, ServiceError> {
+    let original = serde_json::to_value(current).expect("DataDto serializes to JSON");
+    let mut patched = original.clone();
+    json_patch::patch(&mut patched, patch).map_err(|e| match e.kind {
+        PatchErrorKind::TestFailed => ServiceError::Conflict(e.to_string()),
+        _ => ServiceError::Validation(e.to_string()),
+    })?;
+    let name = patched
+        .get("name")
+        .and_then(Value::as_str)
+        .ok_or_else(|| ServiceError::Validation("name must be a string".to_owned()))?
+        .to_owned();
+    let mut expected = original;
+    expected["name"] = Value::from(name.as_str());
+    if patched != expected {
+        return Err(ServiceError::Validation(
+            "only name can be changed".to_owned(),
+        ));
+    }
+    Ok(name)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/data/{id}",
+    tag = "data",
+    params(("id" = i64, Path, description = "Id of the data row")),
+    responses(
+        (status = 204, description = "The row was soft-deleted"),
+        (status = 401, description = "`WRITE_TOKEN` is set and the request doesn't carry it",
+            body = ErrorDto, headers(("WWW-Authenticate" = String, description = "`Bearer`"))),
+        (status = 404, description = "No live row with that id", body = ErrorDto),
+        (status = 409, description = "Violates a unique or foreign key constraint", body = ErrorDto),
+        (status = 500, description = "Repository failure", body = ErrorDto),
+        (status = 503, description = "No pooled connection became available", body = ErrorDto,
+            headers(("Retry-After" = u32, description = "Seconds to wait before retrying"))),
+    )
+)]
+#[inject]
+async fn delete(
+    id: web::Path<DataId>,
+    #[inject] service: Arc<dyn IService>,
+) -> Result<impl Responder, Error> {
+    let affected = service.delete(*id).await?;
+    if affected == 0 {
+        Err(ServiceError::NotFound.into())
+    } else {
+        Ok(HttpResponse::NoContent().finish())
+    }
+}
+
+/// Mounts every supported API version. A new version gets its own scope and config function (and
+/// its own DTOs where the shape changes), leaving the existing versions untouched.
 pub fn route_config(config: &mut ServiceConfig) {
+    config.service(web::scope(V1_PREFIX).configure(v1_config));
+}
+
+fn v1_config(config: &mut ServiceConfig) {
     config.service(
         web::scope("/data")
+            .wrap(RequireWriteToken)
             .route("", web::get().to(get_all))
+            .route("", web::post().to(create))
+            .route("", web::put().to(upsert))
+            .route("", web::route().method(Method::OPTIONS).to(options))
             .route("/", web::get().to(get_all))
+            .route("/", web::post().to(create))
+            .route("/", web::put().to(upsert))
+            .route("/", web::route().method(Method::OPTIONS).to(options))
+            .route("/batch", web::get().to(get_many))
+            .route("/batch", web::post().to(create_many))
+            .route("/batch", web::patch().to(update_many))
+            .route("/count", web::get().to(count))
+            .route("/events", web::get().to(events))
+            .route("/export.csv", web::get().to(export_csv))
+            .route("/merged", web::get().to(get_merged))
+            .route("/overview", web::get().to(get_overview))
+            .route("/random", web::get().to(get_random))
+            .route("/range", web::get().to(get_range))
+            .route("/recent", web::get().to(get_recent))
+            .route("/search", web::get().to(search))
+            .route("/stats", web::get().to(stats))
+            .route("/stream", web::get().to(stream))
             .route("/{id}", web::get().to(get))
+            .route("/{id}", web::head().to(exists))
+            .route("/{id}", web::put().to(update))
+            .route("/{id}", web::patch().to(patch))
+            .route("/{id}", web::delete().to(delete))
             .route("/{id}/{id2}", web::get().to(use_two_deps)),
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
+    use actix_web::App;
+    use serde_json::json;
+
+    fn current() -> DataDto {
+        serde_json::from_value(json!({
+            "id": 1,
+            "name": "Paul",
+            "created_at": "2020-01-02T03:04:05Z",
+            "updated_at": "2020-01-02T03:04:06Z",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn etag_is_built_from_the_row_version() {
+        let created = chrono::DateTime::parse_from_rfc3339("2020-01-02T03:04:05.123456Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        let mut data = Data {
+            id: DataId::from(7),
+            name: "Paul".to_owned(),
+            created_at: created,
+            updated_at: created,
+            deleted_at: None,
+        };
+        assert_eq!(
+            etag(&data, DataFields::ALL).to_string(),
+            "W/\"7-1577934245123456\""
+        );
+        data.deleted_at = Some(created + chrono::Duration::seconds(1));
+        assert_eq!(
+            etag(&data, DataFields::ALL).to_string(),
+            "W/\"7-1577934245123456-1577934246123456\""
+        );
+    }
+
+    #[test]
+    fn etag_names_the_fields_of_a_sparse_body() {
+        let created = chrono::Utc::now();
+        let data = Data {
+            id: DataId::from(7),
+            name: "Paul".to_owned(),
+            created_at: created,
+            updated_at: created,
+            deleted_at: None,
+        };
+        let full = etag(&data, DataFields::ALL);
+        let sparse = etag(&data, DataFields::parse(Some("name,id")).unwrap());
+        assert!(!sparse.weak_eq(&full));
+        assert!(sparse.tag().ends_with(";id,name"));
+
+        // Asking for every field by name is the full representation.
+        let named = DataFields::parse(Some("id,name,created_at,updated_at,deleted_at")).unwrap();
+        assert!(etag(&data, named).weak_eq(&full));
+    }
+
+    #[actix_rt::test]
+    async fn options_lists_methods_and_path_parameters() {
+        let app = init_service(App::new().route(
+            "/api/v1/data",
+            web::route().method(Method::OPTIONS).to(options),
+        ))
+        .await;
+        let req = TestRequest::default()
+            .method(Method::OPTIONS)
+            .uri("/api/v1/data")
+            .to_request();
+        let res = call_service(&app, req).await;
+        assert!(res.status().is_success());
+
+        let allow = res.headers().get("Allow").unwrap().to_str().unwrap();
+        let allow = allow.split(',').map(str::trim).collect::<Vec<_>>();
+        for method in ["GET", "POST", "PUT", "OPTIONS"] {
+            assert!(
+                allow.contains(&method),
+                "{} missing from {:?}",
+                method,
+                allow
+            );
+        }
+
+        let body: Value = read_body_json(res).await;
+        let get = body["operations"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|op| op["method"] == "GET" && op["path"] == "/api/v1/data/{id}")
+            .unwrap();
+        let id = get["parameters"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|param| param["name"] == "id")
+            .unwrap();
+        assert_eq!(id["in"], "path");
+        assert_eq!(id["required"], true);
+        assert_eq!(get["body"], false);
+    }
+
+    fn patch(ops: Value) -> Patch {
+        serde_json::from_value(ops).unwrap()
+    }
+
+    #[test]
+    fn replacing_name_returns_the_new_name() {
+        let patch = patch(json!([
+            {"op": "test", "path": "/name", "value": "Paul"},
+            {"op": "replace", "path": "/name", "value": "Pablo"},
+        ]));
+        assert_eq!(apply_patch(&current(), &patch).unwrap(), "Pablo");
+    }
+
+    #[test]
+    fn failed_test_is_a_conflict() {
+        let patch = patch(json!([
+            {"op": "test", "path": "/name", "value": "Peter"},
+            {"op": "replace", "path": "/name", "value": "Pablo"},
+        ]));
+        let error = apply_patch(&current(), &patch).err().unwrap();
+        assert!(matches!(error, ServiceError::Conflict(_)), "{}", error);
+    }
+
+    #[test]
+    fn read_only_fields_cannot_change() {
+        for (path, value) in [
+            ("/id", json!(2)),
+            ("/created_at", json!("2021-01-01T00:00:00Z")),
+        ] {
+            let patch = patch(json!([{"op": "replace", "path": path, "value": value}]));
+            let error = apply_patch(&current(), &patch).err().unwrap();
+            assert!(matches!(error, ServiceError::Validation(_)), "{}", error);
+        }
+        let patch = patch(json!([{"op": "add", "path": "/extra", "value": 1}]));
+        let error = apply_patch(&current(), &patch).err().unwrap();
+        assert!(matches!(error, ServiceError::Validation(_)), "{}", error);
+    }
+
+    #[test]
+    fn malformed_patches_are_invalid() {
+        let patches = [
+            json!([{"op": "replace", "path": "/missing/deeper", "value": 1}]),
+            json!([{"op": "remove", "path": "/name"}]),
+            json!([{"op": "replace", "path": "/name", "value": 7}]),
+        ];
+        for ops in patches {
+            let error = apply_patch(&current(), &patch(ops)).err().unwrap();
+            assert!(matches!(error, ServiceError::Validation(_)), "{}", error);
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use crate::dtos::data::{
+    CountDto, CreateDataDto, DataDto, DataPageDto, DataSummaryDto, OverviewDto, StatsDto,
+    UpdateDataDto, UpdateManyDto, UpdatedDto,
+};
+use crate::dtos::error::ErrorDto;
+use crate::dtos::operations::{OperationDto, OperationsDto, ParameterDto};
+use crate::dtos::page::PagedDataDto;
+use crate::routes::data;
+use actix_web::web::ServiceConfig;
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        data::get_all,
+        data::options,
+        data::get_merged,
+        data::get_range,
+        data::get_recent,
+        data::get_random,
+        data::create,
+        data::get_many,
+        data::create_many,
+        data::update_many,
+        data::upsert,
+        data::count,
+        data::stats,
+        data::get_overview,
+        data::search,
+        data::stream,
+        data::events,
+        data::export_csv,
+        data::get,
+        data::exists,
+        data::update,
+        data::patch,
+        data::delete,
+        data::use_two_deps,
+    ),
+    components(schemas(
+        DataDto,
+        DataSummaryDto,
+        DataPageDto,
+        PagedDataDto,
+        CountDto,
+        StatsDto,
+        OverviewDto,
+        CreateDataDto,
+        UpdateDataDto,
+        UpdateManyDto,
+        UpdatedDto,
+        OperationsDto,
+        OperationDto,
+        ParameterDto,
+        ErrorDto
+    )),
+    tags((name = "data", description = "Data rows stored in Postgres"))
+)]
+struct ApiDoc;
+
+// Served at /api-docs/openapi.json, and summarized by `OPTIONS /api/v1/data`.
+pub(crate) fn openapi() -> utoipa::openapi::OpenApi {
+    ApiDoc::openapi()
+}
+
+pub fn route_config(config: &mut ServiceConfig) {
+    config.service(SwaggerUi::new("/swagger-ui/{_:.*}").url("/api-docs/openapi.json", openapi()));
+}
@@ -0,0 +1,71 @@
+use crate::dtos::data::DataDto;
+use crate::dtos::page::PageQueryDto;
+use crate::models::data::DataId;
+use crate::models::sort::Sort;
+use crate::services::error::Error as ServiceError;
+use crate::services::service::IService;
+use actix_web::web::{self, ServiceConfig};
+use actix_web::HttpResponse;
+use async_graphql::http::{playground_source, GraphQLPlaygroundConfig};
+use async_graphql::{Context, EmptyMutation, EmptySubscription, Object, Result, Schema};
+use async_graphql_actix_web::{GraphQLRequest, GraphQLResponse};
+use coi_actix_web::inject;
+use std::sync::Arc;
+
+type DataSchema = Schema<Query, EmptyMutation, EmptySubscription>;
+
+pub struct Query;
+
+#[Object]
+impl Query {
+    async fn data(&self, ctx: &Context<'_>, id: i64) -> Result<Option<DataDto>> {
+        let service = ctx.data::<Arc<dyn IService>>()?;
+        match service.get(DataId::from(id), false).await {
+            Ok(data) => Ok(Some(data.into())),
+            Err(ServiceError::NotFound) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn all_data(
+        &self,
+        ctx: &Context<'_>,
+        limit: Option<i64>,
+        offset: Option<i64>,
+    ) -> Result<Vec<DataDto>> {
+        let service = ctx.data::<Arc<dyn IService>>()?;
+        let (limit, offset) = PageQueryDto::new(limit, offset).resolve()?;
+        let data = service
+            .get_all(limit, offset, Sort::default(), false)
+            .await?;
+        Ok(data.into_iter().map(DataDto::from).collect())
+    }
+}
+
+// The schema is shared, but the service comes from the request's coi scope so resolvers see the
+// same registrations as the REST handlers.
+#[inject]
+async fn graphql(
+    schema: web::Data<DataSchema>,
+    request: GraphQLRequest,
+    #[inject] service: Arc<dyn IService>,
+) -> GraphQLResponse {
+    schema
+        .execute(request.into_inner().data(service))
+        .await
+        .into()
+}
+
+async fn playground() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(playground_source(GraphQLPlaygroundConfig::new("/graphql")))
+}
+
+pub fn route_config(config: &mut ServiceConfig) {
+    let schema = Schema::new(Query, EmptyMutation, EmptySubscription);
+    config
+        .app_data(web::Data::new(schema))
+        .route("/graphql", web::post().to(graphql))
+        .route("/playground", web::get().to(playground));
+}
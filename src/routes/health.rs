@@ -0,0 +1,43 @@
+use crate::dtos::health::HealthDto;
+use crate::services::health::{IHealthService, Readiness};
+use actix_web::web::{self, ServiceConfig};
+use actix_web::{HttpResponse, Responder};
+use coi_actix_web::inject;
+
+#[inject]
+async fn health(#[inject] health: Arc<dyn IHealthService>) -> impl Responder {
+    check(&*health).await
+}
+
+async fn check(health: &dyn IHealthService) -> HttpResponse {
+    match health.check().await {
+        Ok(()) => HttpResponse::Ok().json(HealthDto { status: "ok" }),
+        Err(e) => {
+            tracing::warn!("Health check failed: {}", e);
+            HttpResponse::ServiceUnavailable().json(HealthDto { status: "degraded" })
+        }
+    }
+}
+
+// Only shows the process is up and serving requests, it never checks the database.
+async fn live() -> impl Responder {
+    HttpResponse::Ok().json(HealthDto { status: "ok" })
+}
+
+#[inject]
+async fn ready(
+    #[inject] readiness: Arc<Readiness>,
+    #[inject] health: Arc<dyn IHealthService>,
+) -> impl Responder {
+    if !readiness.is_ready() {
+        return HttpResponse::ServiceUnavailable().json(HealthDto { status: "starting" });
+    }
+    check(&*health).await
+}
+
+pub fn route_config(config: &mut ServiceConfig) {
+    config
+        .route("/health", web::get().to(health))
+        .route("/health/live", web::get().to(live))
+        .route("/health/ready", web::get().to(ready));
+}
@@ -0,0 +1,34 @@
+use crate::dtos::metrics::PoolStateDto;
+use crate::metrics::MetricsRegistry;
+use crate::services::metrics::IMetricsService;
+use actix_web::error::ErrorInternalServerError;
+use actix_web::http::header::CONTENT_TYPE;
+use actix_web::web::{self, ServiceConfig};
+use actix_web::{Error, HttpResponse, Responder};
+use coi_actix_web::inject;
+
+#[inject]
+async fn prometheus(
+    #[inject] metrics: Arc<dyn IMetricsService>,
+    #[inject] registry: Arc<MetricsRegistry>,
+) -> Result<impl Responder, Error> {
+    registry.set_pool_state(&metrics.pool_state().await);
+    let (content_type, body) = registry.encode().map_err(ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok()
+        .insert_header((CONTENT_TYPE, content_type))
+        .body(body))
+}
+
+#[inject]
+async fn pool(#[inject] metrics: Arc<dyn IMetricsService>) -> impl Responder {
+    let state = metrics.pool_state().await;
+    HttpResponse::Ok().json(PoolStateDto::from(state))
+}
+
+pub fn route_config(config: &mut ServiceConfig) {
+    config.service(
+        web::scope("/metrics")
+            .route("", web::get().to(prometheus))
+            .route("/pool", web::get().to(pool)),
+    );
+}
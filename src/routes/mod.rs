@@ -1 +1,7 @@
+pub mod admin;
+pub mod content;
 pub mod data;
+pub mod docs;
+pub mod graphql;
+pub mod health;
+pub mod metrics;
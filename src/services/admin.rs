@@ -0,0 +1,75 @@
+use crate::{
+    config::Config,
+    middleware::auth::tokens_match,
+    repositories::repo::IRepository,
+    services::{caching::DataCache, error::Error},
+};
+use async_trait::async_trait;
+use coi::{Container, Inject, Provide};
+use std::sync::Arc;
+use tracing::instrument;
+
+#[async_trait]
+pub trait IAdminService: Inject {
+    async fn reseed(&self) -> Result<i64, Error>;
+}
+
+#[derive(Inject)]
+#[coi(provides pub dyn IAdminService with AdminService::new(repository, cache))]
+struct AdminService {
+    #[coi(inject)]
+    repository: Arc<dyn IRepository>,
+    #[coi(inject)]
+    cache: Arc<DataCache>,
+}
+
+#[async_trait]
+impl IAdminService for AdminService {
+    #[instrument(skip(self), err)]
+    async fn reseed(&self) -> Result<i64, Error> {
+        let result = self.repository.reseed().await.map_err(Into::into);
+        // Every id may now point at a different row.
+        self.cache.clear();
+        result
+    }
+}
+
+impl AdminService {
+    fn new(repository: Arc<dyn IRepository>, cache: Arc<DataCache>) -> Self {
+        Self { repository, cache }
+    }
+}
+
+// The bearer token admin routes require. Without one, every admin request is rejected.
+#[derive(Inject)]
+pub struct AdminToken(Option<String>);
+
+impl AdminToken {
+    pub fn new(token: Option<String>) -> Self {
+        Self(token)
+    }
+
+    pub fn matches(&self, candidate: &str) -> bool {
+        match &self.0 {
+            Some(token) => tokens_match(token, candidate),
+            None => false,
+        }
+    }
+}
+
+// The token is `ADMIN_TOKEN`.
+pub struct AdminTokenProvider;
+
+impl Provide for AdminTokenProvider {
+    type Output = AdminToken;
+
+    fn provide(&self, container: &Container) -> coi::Result<Arc<AdminToken>> {
+        let config = container.resolve::<Config>("config")?;
+        Ok(Arc::new(AdminToken::new(config.admin_token.clone())))
+    }
+
+    #[cfg(feature = "debug")]
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["config"]
+    }
+}
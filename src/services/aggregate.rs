@@ -0,0 +1,78 @@
+use crate::{
+    models::{
+        data::Data,
+        sort::{Sort, SortField, SortOrder},
+    },
+    repositories::repo::{DbData, IRepository},
+    services::error::Error,
+};
+use async_trait::async_trait;
+use coi::Inject;
+use futures::future::try_join;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::sync::Arc;
+use tracing::instrument;
+
+#[async_trait]
+pub trait IAggregateService: Inject {
+    async fn get_all(&self, limit: i64, offset: i64, sort: Sort) -> Result<Vec<Data>, Error>;
+}
+
+// Combines the configured repository with the in-memory one, to show two implementations of the
+// same trait being injected side by side under different keys.
+#[derive(Inject)]
+#[coi(provides pub dyn IAggregateService with AggregateService::new(repository, memory_repository))]
+struct AggregateService {
+    #[coi(inject)]
+    repository: Arc<dyn IRepository>,
+    #[coi(inject)]
+    memory_repository: Arc<dyn IRepository>,
+}
+
+#[async_trait]
+impl IAggregateService for AggregateService {
+    #[instrument(skip(self), err)]
+    async fn get_all(&self, limit: i64, offset: i64, sort: Sort) -> Result<Vec<Data>, Error> {
+        // A page of the merged rows can come from anywhere in the first `offset + limit` rows of
+        // either repository, so both are read from the start and paged after merging.
+        let end = offset.saturating_add(limit);
+        let (primary, secondary) = try_join(
+            self.repository.get_all(end, 0, sort, false),
+            self.memory_repository.get_all(end, 0, sort, false),
+        )
+        .await?;
+
+        // Rows in the configured repository win when both have the same id.
+        let ids = primary.iter().map(|data| data.id).collect::<HashSet<_>>();
+        let mut rows = primary;
+        rows.extend(secondary.into_iter().filter(|data| !ids.contains(&data.id)));
+        rows.sort_by(|a, b| compare(a, b, sort));
+        rows.into_iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .map(|row| Data::try_from(row).map_err(Into::into))
+            .collect()
+    }
+}
+
+impl AggregateService {
+    fn new(repository: Arc<dyn IRepository>, memory_repository: Arc<dyn IRepository>) -> Self {
+        Self {
+            repository,
+            memory_repository,
+        }
+    }
+}
+
+fn compare(a: &DbData, b: &DbData, sort: Sort) -> Ordering {
+    let ordering = match sort.field {
+        SortField::Id => a.id.cmp(&b.id),
+        SortField::Name => a.name.cmp(&b.name).then(a.id.cmp(&b.id)),
+    };
+    match sort.order {
+        SortOrder::Asc => ordering,
+        SortOrder::Desc => ordering.reverse(),
+    }
+}
@@ -0,0 +1,206 @@
+use crate::{
+    config::Config,
+    models::{
+        data::{Data, DataId, DataStats},
+        sort::Sort,
+    },
+    services::{error::Error, service::IService},
+};
+use async_trait::async_trait;
+use coi::{Container, Inject, Provide};
+use futures::stream::BoxStream;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::instrument;
+
+#[derive(Inject)]
+pub struct DataCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<DataId, (Instant, Data)>>,
+}
+
+impl DataCache {
+    fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn get(&self, id: DataId) -> Option<Data> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(&id) {
+            Some((inserted, data)) if inserted.elapsed() < self.ttl => Some(data.clone()),
+            Some(_) => {
+                entries.remove(&id);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn insert(&self, data: &Data) {
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(data.id, (Instant::now(), data.clone()));
+    }
+
+    pub(crate) fn invalidate(&self, id: DataId) {
+        self.entries.lock().unwrap().remove(&id);
+    }
+
+    pub(crate) fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+// Entries live for `CACHE_TTL_SECS`.
+pub struct DataCacheProvider;
+
+impl Provide for DataCacheProvider {
+    type Output = DataCache;
+
+    fn provide(&self, container: &Container) -> coi::Result<Arc<DataCache>> {
+        let config = container.resolve::<Config>("config")?;
+        Ok(Arc::new(DataCache::new(config.cache_ttl)))
+    }
+
+    #[cfg(feature = "debug")]
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["config"]
+    }
+}
+
+#[derive(Inject)]
+#[coi(provides pub dyn IService with CachingService::new(uncached_service, cache))]
+struct CachingService {
+    #[coi(inject)]
+    uncached_service: Arc<dyn IService>,
+    #[coi(inject)]
+    cache: Arc<DataCache>,
+}
+
+#[async_trait]
+impl IService for CachingService {
+    #[instrument(skip(self), err)]
+    async fn get(&self, id: DataId, include_deleted: bool) -> Result<Data, Error> {
+        // Only live rows are cached; asking for deleted rows always goes to the repository.
+        if include_deleted {
+            return self.uncached_service.get(id, include_deleted).await;
+        }
+        if let Some(data) = self.cache.get(id) {
+            return Ok(data);
+        }
+        let data = self.uncached_service.get(id, include_deleted).await?;
+        self.cache.insert(&data);
+        Ok(data)
+    }
+
+    async fn exists(&self, id: DataId) -> Result<bool, Error> {
+        // Deleting a row evicts it, so a cached row is still live.
+        if self.cache.get(id).is_some() {
+            return Ok(true);
+        }
+        self.uncached_service.exists(id).await
+    }
+
+    async fn get_all(
+        &self,
+        limit: i64,
+        offset: i64,
+        sort: Sort,
+        include_deleted: bool,
+    ) -> Result<Vec<Data>, Error> {
+        self.uncached_service
+            .get_all(limit, offset, sort, include_deleted)
+            .await
+    }
+
+    async fn get_after(&self, after: i64, limit: i64) -> Result<Vec<Data>, Error> {
+        self.uncached_service.get_after(after, limit).await
+    }
+
+    async fn get_range(&self, from: i64, to: i64) -> Result<Vec<Data>, Error> {
+        self.uncached_service.get_range(from, to).await
+    }
+
+    async fn get_recent(&self, limit: i64) -> Result<Vec<Data>, Error> {
+        self.uncached_service.get_recent(limit).await
+    }
+
+    async fn get_random(&self) -> Result<Data, Error> {
+        self.uncached_service.get_random().await
+    }
+
+    async fn stream_all(
+        &self,
+        sort: Sort,
+    ) -> Result<BoxStream<'static, Result<Data, Error>>, Error> {
+        self.uncached_service.stream_all(sort).await
+    }
+
+    async fn get_many(&self, ids: &[i64]) -> Result<Vec<Data>, Error> {
+        self.uncached_service.get_many(ids).await
+    }
+
+    async fn insert(&self, name: &str) -> Result<Data, Error> {
+        self.uncached_service.insert(name).await
+    }
+
+    async fn insert_many(&self, names: &[String]) -> Result<Vec<Data>, Error> {
+        self.uncached_service.insert_many(names).await
+    }
+
+    #[instrument(skip(self), err)]
+    async fn upsert(&self, name: &str) -> Result<Data, Error> {
+        let data = self.uncached_service.upsert(name).await?;
+        self.cache.invalidate(data.id);
+        Ok(data)
+    }
+
+    #[instrument(skip(self), err)]
+    async fn update(&self, id: DataId, name: &str) -> Result<Data, Error> {
+        let result = self.uncached_service.update(id, name).await;
+        self.cache.invalidate(id);
+        result
+    }
+
+    #[instrument(skip(self), err)]
+    async fn update_many(&self, ids: &[i64], name: &str) -> Result<u64, Error> {
+        let result = self.uncached_service.update_many(ids, name).await;
+        for id in ids {
+            self.cache.invalidate(DataId::from(*id));
+        }
+        result
+    }
+
+    #[instrument(skip(self), err)]
+    async fn delete(&self, id: DataId) -> Result<u64, Error> {
+        let result = self.uncached_service.delete(id).await;
+        self.cache.invalidate(id);
+        result
+    }
+
+    async fn count(&self) -> Result<i64, Error> {
+        self.uncached_service.count().await
+    }
+
+    async fn stats(&self) -> Result<DataStats, Error> {
+        self.uncached_service.stats().await
+    }
+
+    async fn search(&self, term: &str) -> Result<Vec<Data>, Error> {
+        self.uncached_service.search(term).await
+    }
+}
+
+impl CachingService {
+    fn new(uncached_service: Arc<dyn IService>, cache: Arc<DataCache>) -> Self {
+        Self {
+            uncached_service,
+            cache,
+        }
+    }
+}
@@ -1,9 +1,145 @@
-use actix_web::ResponseError;
+use crate::dtos::error::ErrorDto;
+use crate::repositories::error::Error as RepoError;
+use actix_web::http::{header, StatusCode};
+use actix_web::{HttpResponse, ResponseError};
+use std::collections::BTreeMap;
+use validator::{ValidationError, ValidationErrors, ValidationErrorsKind};
+
+// Seconds clients are asked to wait before retrying when the connection pool is exhausted.
+const RETRY_AFTER_SECS: u32 = 1;
+
+// Validation messages keyed by the path of the offending field, e.g. `name` or `[2].name`.
+pub type FieldErrors = BTreeMap<String, Vec<String>>;
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("Error calling repository: {0}")]
-    RepoError(#[from] Box<dyn std::error::Error + Send + Sync + 'static>),
+    Repo(#[source] RepoError),
+    #[error("Resource not found")]
+    NotFound,
+    #[error("Conflict: {0}")]
+    Conflict(String),
+    #[error("Service unavailable, try again later")]
+    Unavailable,
+    #[error("Database query timed out")]
+    Timeout,
+    #[error("Missing or invalid bearer token")]
+    Unauthorized,
+    #[error("Validation failed: {0}")]
+    Validation(String),
+    #[error("Invalid input")]
+    Invalid(FieldErrors),
+}
+
+impl Error {
+    fn code(&self) -> &'static str {
+        match self {
+            Error::Repo(_) => "REPO_ERROR",
+            Error::NotFound => "NOT_FOUND",
+            Error::Conflict(_) => "CONFLICT",
+            Error::Unavailable => "UNAVAILABLE",
+            Error::Timeout => "QUERY_TIMEOUT",
+            Error::Unauthorized => "UNAUTHORIZED",
+            Error::Validation(_) => "VALIDATION_ERROR",
+            Error::Invalid(_) => "INVALID_INPUT",
+        }
+    }
+
+    // The body sent for this error. `RequestIdHeader` rebuilds it to fill in the request id.
+    pub(crate) fn to_dto(&self) -> ErrorDto {
+        ErrorDto {
+            error: self.to_string(),
+            code: self.code(),
+            errors: match self {
+                Error::Invalid(errors) => Some(errors.clone()),
+                _ => None,
+            },
+            request_id: None,
+        }
+    }
+}
+
+impl From<ValidationErrors> for Error {
+    fn from(errors: ValidationErrors) -> Self {
+        let mut fields = FieldErrors::new();
+        field_errors("", &errors, &mut fields);
+        Error::Invalid(fields)
+    }
 }
 
-impl ResponseError for Error {}
+// Adds the messages in `errors` to `fields`, with nested structs and lists flattened into paths
+// under `path`.
+pub fn field_errors(path: &str, errors: &ValidationErrors, fields: &mut FieldErrors) {
+    for (field, kind) in errors.errors() {
+        let path = match path {
+            "" => field.to_string(),
+            _ => format!("{}.{}", path, field),
+        };
+        match kind {
+            ValidationErrorsKind::Field(errors) => fields
+                .entry(path)
+                .or_default()
+                .extend(errors.iter().map(message)),
+            ValidationErrorsKind::Struct(errors) => field_errors(&path, errors, fields),
+            ValidationErrorsKind::List(items) => {
+                for (index, errors) in items {
+                    field_errors(&format!("{}[{}]", path, index), errors, fields);
+                }
+            }
+        }
+    }
+}
+
+// Validators without a message fall back to their code, e.g. `length`.
+fn message(error: &ValidationError) -> String {
+    match &error.message {
+        Some(message) => message.to_string(),
+        None => error.code.to_string(),
+    }
+}
+
+impl From<RepoError> for Error {
+    fn from(e: RepoError) -> Self {
+        match e {
+            RepoError::NotFound => Error::NotFound,
+            RepoError::PoolTimeout | RepoError::CircuitOpen => Error::Unavailable,
+            RepoError::QueryTimeout => Error::Timeout,
+            // Only reachable by skipping DTO validation, so reported the same way it would have been.
+            RepoError::InvalidName(message) => Error::Invalid(FieldErrors::from([(
+                "name".to_owned(),
+                vec![message.to_owned()],
+            )])),
+            e if e.is_conflict() => Error::Conflict(e.to_string()),
+            e => Error::Repo(e),
+        }
+    }
+}
+
+impl ResponseError for Error {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Error::Repo(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Error::NotFound => StatusCode::NOT_FOUND,
+            Error::Conflict(_) => StatusCode::CONFLICT,
+            Error::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+            Error::Timeout => StatusCode::GATEWAY_TIMEOUT,
+            Error::Unauthorized => StatusCode::UNAUTHORIZED,
+            Error::Validation(_) => StatusCode::BAD_REQUEST,
+            Error::Invalid(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        match self {
+            Error::Unavailable => {
+                response.insert_header((header::RETRY_AFTER, RETRY_AFTER_SECS));
+            }
+            Error::Unauthorized => {
+                response.insert_header((header::WWW_AUTHENTICATE, "Bearer"));
+            }
+            _ => {}
+        }
+        response.json(self.to_dto())
+    }
+}
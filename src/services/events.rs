@@ -0,0 +1,49 @@
+use crate::models::data::Data;
+use coi::{Inject, Provide};
+use futures::channel::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+
+// Rows a subscriber can fall behind by before it is disconnected.
+const SUBSCRIBER_BUFFER: usize = 64;
+
+// Hands every newly inserted row to the open `GET /api/v1/data/events` streams. Rows are published
+// by the listener, which hears about them from a trigger on the `data` table, so inserts made
+// through other servers (or by hand) are included.
+#[derive(Inject, Default)]
+pub struct DataEvents {
+    subscribers: Mutex<Vec<Sender<Data>>>,
+}
+
+impl DataEvents {
+    pub fn subscribe(&self) -> Receiver<Data> {
+        let (sender, receiver) = mpsc::channel(SUBSCRIBER_BUFFER);
+        self.subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+
+    // Lets the listener skip looking rows up when nobody would receive them.
+    pub(crate) fn has_subscribers(&self) -> bool {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.retain(|subscriber| !subscriber.is_closed());
+        !subscribers.is_empty()
+    }
+
+    pub(crate) fn publish(&self, data: &Data) {
+        self.subscribers.lock().unwrap().retain_mut(|subscriber| {
+            match subscriber.try_send(data.clone()) {
+                Ok(()) => true,
+                Err(e) => {
+                    // Dropping the sender ends the stream, and the client can reconnect.
+                    if e.is_full() {
+                        tracing::warn!("Disconnecting a data event subscriber that fell behind");
+                    }
+                    false
+                }
+            }
+        });
+    }
+}
+
+#[derive(Provide)]
+#[coi(provides DataEvents with DataEvents::default())]
+pub struct DataEventsProvider;
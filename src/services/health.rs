@@ -0,0 +1,50 @@
+use crate::{repositories::repo::IRepository, services::error::Error};
+use async_trait::async_trait;
+use coi::{Inject, Provide};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tracing::instrument;
+
+#[async_trait]
+pub trait IHealthService: Inject {
+    async fn check(&self) -> Result<(), Error>;
+}
+
+#[derive(Inject)]
+#[coi(provides pub dyn IHealthService with HealthService::new(repository))]
+struct HealthService {
+    #[coi(inject)]
+    repository: Arc<dyn IRepository>,
+}
+
+#[async_trait]
+impl IHealthService for HealthService {
+    #[instrument(skip(self), err)]
+    async fn check(&self) -> Result<(), Error> {
+        self.repository.ping().await.map_err(Into::into)
+    }
+}
+
+impl HealthService {
+    fn new(repository: Arc<dyn IRepository>) -> Self {
+        Self { repository }
+    }
+}
+
+// Whether startup (pool warmup) has finished, so readiness probes can fail until then.
+#[derive(Default, Inject)]
+pub struct Readiness(AtomicBool);
+
+impl Readiness {
+    pub fn mark_ready(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+}
+
+#[derive(Provide)]
+#[coi(provides Readiness with Readiness::default())]
+pub struct ReadinessProvider;
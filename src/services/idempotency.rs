@@ -0,0 +1,139 @@
+use crate::config::Config;
+use crate::models::data::Data;
+use coi::{Container, Inject, Provide};
+use futures::lock::Mutex as AsyncMutex;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// At most this many keys are remembered. Past that, the oldest ones are forgotten early.
+const MAX_TRACKED_KEYS: usize = 10_000;
+
+// The name that was requested and the row that was created for it.
+pub type IdempotentResult = Option<(String, Data)>;
+
+struct Entry {
+    created: Instant,
+    slot: Arc<AsyncMutex<IdempotentResult>>,
+}
+
+// Every key is queued in the order it was created, so expired and evicted keys are always at the
+// front and each request only has to look there.
+#[derive(Default)]
+struct Entries {
+    slots: HashMap<String, Entry>,
+    order: VecDeque<String>,
+}
+
+impl Entries {
+    fn expire(&mut self, now: Instant, ttl: Duration) {
+        while let Some(key) = self.order.front() {
+            if now.duration_since(self.slots[key].created) < ttl {
+                break;
+            }
+            self.evict_oldest();
+        }
+    }
+
+    fn evict_oldest(&mut self) {
+        if let Some(key) = self.order.pop_front() {
+            self.slots.remove(&key);
+        }
+    }
+}
+
+// Remembers the outcome of `POST /data` per `Idempotency-Key` for `ttl`. Each key has its own lock,
+// so concurrent requests with the same key run one after the other and later ones see the result.
+#[derive(Inject)]
+pub struct IdempotencyStore {
+    ttl: Duration,
+    max_keys: usize,
+    entries: Mutex<Entries>,
+}
+
+impl IdempotencyStore {
+    fn new(ttl: Duration, max_keys: usize) -> Self {
+        Self {
+            ttl,
+            max_keys,
+            entries: Mutex::new(Entries::default()),
+        }
+    }
+
+    pub fn slot(&self, key: &str) -> Arc<AsyncMutex<IdempotentResult>> {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        entries.expire(now, self.ttl);
+        if let Some(entry) = entries.slots.get(key) {
+            return entry.slot.clone();
+        }
+        if entries.slots.len() >= self.max_keys {
+            entries.evict_oldest();
+        }
+        let slot = Arc::new(AsyncMutex::new(None));
+        entries.slots.insert(
+            key.to_owned(),
+            Entry {
+                created: now,
+                slot: slot.clone(),
+            },
+        );
+        entries.order.push_back(key.to_owned());
+        slot
+    }
+}
+
+// Keys are remembered for `IDEMPOTENCY_TTL_SECS`.
+pub struct IdempotencyStoreProvider;
+
+impl Provide for IdempotencyStoreProvider {
+    type Output = IdempotencyStore;
+
+    fn provide(&self, container: &Container) -> coi::Result<Arc<IdempotencyStore>> {
+        let config = container.resolve::<Config>("config")?;
+        Ok(Arc::new(IdempotencyStore::new(
+            config.idempotency_ttl,
+            MAX_TRACKED_KEYS,
+        )))
+    }
+
+    #[cfg(feature = "debug")]
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["config"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_key_shares_a_slot() {
+        let store = IdempotencyStore::new(Duration::from_secs(60), 10);
+        assert!(Arc::ptr_eq(&store.slot("a"), &store.slot("a")));
+        assert!(!Arc::ptr_eq(&store.slot("a"), &store.slot("b")));
+    }
+
+    #[test]
+    fn forgets_the_oldest_key_when_full() {
+        let store = IdempotencyStore::new(Duration::from_secs(60), 3);
+        let first = store.slot("a");
+        for key in ["b", "c", "d", "e"] {
+            store.slot(key);
+        }
+        let entries = store.entries.lock().unwrap();
+        assert_eq!(entries.slots.len(), 3);
+        assert_eq!(entries.order, ["c", "d", "e"]);
+        drop(entries);
+        assert!(!Arc::ptr_eq(&first, &store.slot("a")));
+    }
+
+    #[test]
+    fn forgets_expired_keys() {
+        let store = IdempotencyStore::new(Duration::ZERO, 10);
+        let first = store.slot("a");
+        store.slot("b");
+        assert!(!Arc::ptr_eq(&first, &store.slot("a")));
+        assert_eq!(store.entries.lock().unwrap().slots.len(), 1);
+    }
+}
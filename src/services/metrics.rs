@@ -0,0 +1,37 @@
+use crate::postgres::{PoolState, PostgresPool};
+use async_trait::async_trait;
+use coi::Inject;
+use mobc_postgres::mobc::Manager;
+use mobc_postgres::PgConnectionManager;
+use std::sync::Arc;
+
+#[async_trait]
+pub trait IMetricsService: Inject {
+    async fn pool_state(&self) -> PoolState;
+}
+
+#[derive(Inject)]
+#[coi(provides pub dyn IMetricsService with MetricsService::<T>::new(pool))]
+struct MetricsService<T>(#[coi(inject = "pool")] Arc<PostgresPool<T>>)
+where
+    PgConnectionManager<T>: Manager;
+
+#[async_trait]
+impl<T> IMetricsService for MetricsService<T>
+where
+    PgConnectionManager<T>: Manager,
+    T: Send + Sync + 'static,
+{
+    async fn pool_state(&self) -> PoolState {
+        self.0.state().await
+    }
+}
+
+impl<T> MetricsService<T>
+where
+    PgConnectionManager<T>: Manager,
+{
+    fn new(pool: Arc<PostgresPool<T>>) -> Self {
+        Self(pool)
+    }
+}
@@ -1,2 +1,10 @@
+pub mod admin;
+pub mod aggregate;
+pub mod caching;
 pub mod error;
+pub mod events;
+pub mod health;
+pub mod idempotency;
+pub mod metrics;
 pub mod service;
+pub mod snapshot;
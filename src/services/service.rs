@@ -1,12 +1,47 @@
-use crate::{models::data::Data, repositories::repo::IRepository, services::error::Error};
+use crate::{
+    models::{
+        data::{Data, DataId, DataStats},
+        sort::Sort,
+    },
+    repositories::repo::IRepository,
+    services::error::Error,
+};
 use async_trait::async_trait;
 use coi::Inject;
+use futures::stream::{BoxStream, StreamExt};
+use std::convert::TryFrom;
 use std::sync::Arc;
+use tracing::instrument;
 
 #[async_trait]
 pub trait IService: Inject {
-    async fn get(&self, id: i64) -> Result<Data, Error>;
-    async fn get_all(&self) -> Result<Vec<Data>, Error>;
+    async fn get(&self, id: DataId, include_deleted: bool) -> Result<Data, Error>;
+    async fn exists(&self, id: DataId) -> Result<bool, Error>;
+    async fn get_all(
+        &self,
+        limit: i64,
+        offset: i64,
+        sort: Sort,
+        include_deleted: bool,
+    ) -> Result<Vec<Data>, Error>;
+    async fn get_after(&self, after: i64, limit: i64) -> Result<Vec<Data>, Error>;
+    async fn get_range(&self, from: i64, to: i64) -> Result<Vec<Data>, Error>;
+    async fn get_recent(&self, limit: i64) -> Result<Vec<Data>, Error>;
+    async fn get_random(&self) -> Result<Data, Error>;
+    async fn get_many(&self, ids: &[i64]) -> Result<Vec<Data>, Error>;
+    async fn stream_all(
+        &self,
+        sort: Sort,
+    ) -> Result<BoxStream<'static, Result<Data, Error>>, Error>;
+    async fn insert(&self, name: &str) -> Result<Data, Error>;
+    async fn insert_many(&self, names: &[String]) -> Result<Vec<Data>, Error>;
+    async fn upsert(&self, name: &str) -> Result<Data, Error>;
+    async fn update(&self, id: DataId, name: &str) -> Result<Data, Error>;
+    async fn update_many(&self, ids: &[i64], name: &str) -> Result<u64, Error>;
+    async fn delete(&self, id: DataId) -> Result<u64, Error>;
+    async fn count(&self) -> Result<i64, Error>;
+    async fn stats(&self) -> Result<DataStats, Error>;
+    async fn search(&self, term: &str) -> Result<Vec<Data>, Error>;
 }
 
 #[derive(Inject)]
@@ -18,20 +53,157 @@ struct Service {
 
 #[async_trait]
 impl IService for Service {
-    async fn get(&self, id: i64) -> Result<Data, Error> {
+    #[instrument(skip(self), err)]
+    async fn get(&self, id: DataId, include_deleted: bool) -> Result<Data, Error> {
         self.repository
-            .get(id)
+            .get(id, include_deleted)
             .await
-            .map(Into::into)
-            .map_err(|e| (Box::new(e) as Box<dyn std::error::Error + Send + Sync>).into())
+            .and_then(Data::try_from)
+            .map_err(Into::into)
     }
 
-    async fn get_all(&self) -> Result<Vec<Data>, Error> {
+    #[instrument(skip(self), err)]
+    async fn exists(&self, id: DataId) -> Result<bool, Error> {
+        self.repository.exists(id).await.map_err(Into::into)
+    }
+
+    #[instrument(skip(self), err)]
+    async fn get_all(
+        &self,
+        limit: i64,
+        offset: i64,
+        sort: Sort,
+        include_deleted: bool,
+    ) -> Result<Vec<Data>, Error> {
+        self.repository
+            .get_all(limit, offset, sort, include_deleted)
+            .await
+            .and_then(|v| v.into_iter().map(Data::try_from).collect())
+            .map_err(Into::into)
+    }
+
+    #[instrument(skip(self), err)]
+    async fn get_after(&self, after: i64, limit: i64) -> Result<Vec<Data>, Error> {
+        self.repository
+            .get_after(after, limit)
+            .await
+            .and_then(|v| v.into_iter().map(Data::try_from).collect())
+            .map_err(Into::into)
+    }
+
+    #[instrument(skip(self), err)]
+    async fn get_range(&self, from: i64, to: i64) -> Result<Vec<Data>, Error> {
+        self.repository
+            .get_range(from, to)
+            .await
+            .and_then(|v| v.into_iter().map(Data::try_from).collect())
+            .map_err(Into::into)
+    }
+
+    #[instrument(skip(self), err)]
+    async fn get_recent(&self, limit: i64) -> Result<Vec<Data>, Error> {
+        self.repository
+            .get_recent(limit)
+            .await
+            .and_then(|v| v.into_iter().map(Data::try_from).collect())
+            .map_err(Into::into)
+    }
+
+    #[instrument(skip(self), err)]
+    async fn get_random(&self) -> Result<Data, Error> {
+        self.repository
+            .get_random()
+            .await
+            .and_then(Data::try_from)
+            .map_err(Into::into)
+    }
+
+    #[instrument(skip(self), err)]
+    async fn stream_all(
+        &self,
+        sort: Sort,
+    ) -> Result<BoxStream<'static, Result<Data, Error>>, Error> {
+        let data = self.repository.stream_all(sort).await?;
+        Ok(data
+            .map(|row| row.and_then(Data::try_from).map_err(Into::into))
+            .boxed())
+    }
+
+    #[instrument(skip(self), err)]
+    async fn get_many(&self, ids: &[i64]) -> Result<Vec<Data>, Error> {
+        self.repository
+            .get_many(ids)
+            .await
+            .and_then(|v| v.into_iter().map(Data::try_from).collect())
+            .map_err(Into::into)
+    }
+
+    #[instrument(skip(self), err)]
+    async fn insert(&self, name: &str) -> Result<Data, Error> {
+        self.repository
+            .insert(name)
+            .await
+            .and_then(Data::try_from)
+            .map_err(Into::into)
+    }
+
+    #[instrument(skip(self), err)]
+    async fn insert_many(&self, names: &[String]) -> Result<Vec<Data>, Error> {
+        self.repository
+            .insert_many(names)
+            .await
+            .and_then(|v| v.into_iter().map(Data::try_from).collect())
+            .map_err(Into::into)
+    }
+
+    #[instrument(skip(self), err)]
+    async fn upsert(&self, name: &str) -> Result<Data, Error> {
+        self.repository
+            .upsert(name)
+            .await
+            .and_then(Data::try_from)
+            .map_err(Into::into)
+    }
+
+    #[instrument(skip(self), err)]
+    async fn update(&self, id: DataId, name: &str) -> Result<Data, Error> {
+        self.repository
+            .update(id, name)
+            .await
+            .and_then(Data::try_from)
+            .map_err(Into::into)
+    }
+
+    #[instrument(skip(self), err)]
+    async fn update_many(&self, ids: &[i64], name: &str) -> Result<u64, Error> {
+        self.repository
+            .update_many(ids, name)
+            .await
+            .map_err(Into::into)
+    }
+
+    #[instrument(skip(self), err)]
+    async fn delete(&self, id: DataId) -> Result<u64, Error> {
+        self.repository.delete(id).await.map_err(Into::into)
+    }
+
+    #[instrument(skip(self), err)]
+    async fn count(&self) -> Result<i64, Error> {
+        self.repository.count().await.map_err(Into::into)
+    }
+
+    #[instrument(skip(self), err)]
+    async fn stats(&self) -> Result<DataStats, Error> {
+        self.repository.stats().await.map_err(Into::into)
+    }
+
+    #[instrument(skip(self), err)]
+    async fn search(&self, term: &str) -> Result<Vec<Data>, Error> {
         self.repository
-            .get_all()
+            .search(term)
             .await
-            .map(|v| v.into_iter().map(Into::into).collect())
-            .map_err(|e| (Box::new(e) as Box<dyn std::error::Error + Send + Sync>).into())
+            .and_then(|v| v.into_iter().map(Data::try_from).collect())
+            .map_err(Into::into)
     }
 }
 
@@ -40,3 +212,215 @@ impl Service {
         Self { repository }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::error::Error as RepoError;
+    use crate::repositories::repo::DbData;
+    use chrono::{TimeZone, Utc};
+    use mobc_postgres::mobc::Error as MobcError;
+    use mockall::mock;
+    use mockall::predicate::eq;
+
+    mock! {
+        Repository {}
+
+        impl Inject for Repository {}
+
+        #[async_trait]
+        impl IRepository for Repository {
+            async fn get(&self, id: DataId, include_deleted: bool) -> Result<DbData, RepoError>;
+            async fn exists(&self, id: DataId) -> Result<bool, RepoError>;
+            async fn get_all(&self, limit: i64, offset: i64, sort: Sort, include_deleted: bool)
+                -> Result<Vec<DbData>, RepoError>;
+            async fn get_after(&self, after: i64, limit: i64) -> Result<Vec<DbData>, RepoError>;
+            async fn get_range(&self, from: i64, to: i64) -> Result<Vec<DbData>, RepoError>;
+            async fn get_recent(&self, limit: i64) -> Result<Vec<DbData>, RepoError>;
+            async fn get_random(&self) -> Result<DbData, RepoError>;
+            async fn get_many(&self, ids: &[i64]) -> Result<Vec<DbData>, RepoError>;
+            async fn stream_all(&self, sort: Sort)
+                -> Result<BoxStream<'static, Result<DbData, RepoError>>, RepoError>;
+            async fn insert(&self, name: &str) -> Result<DbData, RepoError>;
+            async fn insert_many(&self, names: &[String]) -> Result<Vec<DbData>, RepoError>;
+            async fn upsert(&self, name: &str) -> Result<DbData, RepoError>;
+            async fn update(&self, id: DataId, name: &str) -> Result<DbData, RepoError>;
+            async fn update_many(&self, ids: &[i64], name: &str) -> Result<u64, RepoError>;
+            async fn delete(&self, id: DataId) -> Result<u64, RepoError>;
+            async fn count(&self) -> Result<i64, RepoError>;
+            async fn stats(&self) -> Result<DataStats, RepoError>;
+            async fn search(&self, term: &str) -> Result<Vec<DbData>, RepoError>;
+            async fn ping(&self) -> Result<(), RepoError>;
+            async fn reseed(&self) -> Result<i64, RepoError>;
+        }
+    }
+
+    fn db_data(id: i64, name: &str) -> DbData {
+        let timestamp = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        DbData {
+            id: DataId::from(id),
+            name: name.to_owned(),
+            created_at: timestamp,
+            updated_at: timestamp,
+            deleted_at: None,
+        }
+    }
+
+    #[actix_rt::test]
+    async fn get_maps_repository_row_to_data() {
+        let mut repository = MockRepository::new();
+        repository
+            .expect_get()
+            .with(eq(DataId::from(7)), eq(false))
+            .times(1)
+            .returning(|id, _| Ok(db_data(id.into(), "Paul")));
+        let service = Service::new(Arc::new(repository));
+
+        let data = service.get(DataId::from(7), false).await.unwrap();
+        assert_eq!(data.id, DataId::from(7));
+        assert_eq!(data.name, "Paul");
+        assert_eq!(data.created_at, db_data(7, "Paul").created_at);
+    }
+
+    #[actix_rt::test]
+    async fn get_maps_missing_row_to_not_found() {
+        let mut repository = MockRepository::new();
+        repository
+            .expect_get()
+            .returning(|_, _| Err(RepoError::NotFound));
+        let service = Service::new(Arc::new(repository));
+
+        let error = service.get(DataId::from(7), false).await.err().unwrap();
+        assert!(matches!(error, Error::NotFound));
+    }
+
+    #[actix_rt::test]
+    async fn get_maps_pool_timeout_to_unavailable() {
+        let mut repository = MockRepository::new();
+        repository
+            .expect_get()
+            .returning(|_, _| Err(RepoError::PoolTimeout));
+        let service = Service::new(Arc::new(repository));
+
+        let error = service.get(DataId::from(7), false).await.err().unwrap();
+        assert!(matches!(error, Error::Unavailable));
+    }
+
+    #[actix_rt::test]
+    async fn get_maps_query_timeout_to_timeout() {
+        let mut repository = MockRepository::new();
+        repository
+            .expect_get()
+            .returning(|_, _| Err(RepoError::QueryTimeout));
+        let service = Service::new(Arc::new(repository));
+
+        let error = service.get(DataId::from(7), false).await.err().unwrap();
+        assert!(matches!(error, Error::Timeout));
+    }
+
+    #[actix_rt::test]
+    async fn insert_reports_invalid_name_as_field_error() {
+        let mut repository = MockRepository::new();
+        repository
+            .expect_insert()
+            .returning(|_| Err(RepoError::InvalidName("must not be blank")));
+        let service = Service::new(Arc::new(repository));
+
+        let error = service.insert("  ").await.err().unwrap();
+        match error {
+            Error::Invalid(fields) => assert_eq!(fields["name"], ["must not be blank"]),
+            e => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn get_keeps_repository_error_type() {
+        let mut repository = MockRepository::new();
+        repository
+            .expect_get()
+            .returning(|_, _| Err(RepoError::Pool(MobcError::BadConn)));
+        let service = Service::new(Arc::new(repository));
+
+        let error = service.get(DataId::from(7), false).await.err().unwrap();
+        assert!(matches!(
+            error,
+            Error::Repo(RepoError::Pool(MobcError::BadConn))
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn get_rejects_row_with_empty_name() {
+        let mut repository = MockRepository::new();
+        repository
+            .expect_get()
+            .returning(|id, _| Ok(db_data(id.into(), " ")));
+        let service = Service::new(Arc::new(repository));
+
+        let error = service.get(DataId::from(7), false).await.err().unwrap();
+        assert!(
+            matches!(error, Error::Repo(RepoError::InvalidRow(id, _)) if id == DataId::from(7))
+        );
+    }
+
+    #[actix_rt::test]
+    async fn exists_does_not_fetch_the_row() {
+        let mut repository = MockRepository::new();
+        repository.expect_get().never();
+        repository
+            .expect_exists()
+            .with(eq(DataId::from(7)))
+            .times(1)
+            .returning(|_| Ok(false));
+        let service = Service::new(Arc::new(repository));
+
+        assert!(!service.exists(DataId::from(7)).await.unwrap());
+    }
+
+    #[actix_rt::test]
+    async fn get_all_rejects_page_with_invalid_row() {
+        let mut repository = MockRepository::new();
+        repository.expect_get_all().returning(|_, _, _, _| {
+            let mut invalid = db_data(3, "Santiago");
+            invalid.updated_at = invalid.created_at - chrono::Duration::seconds(1);
+            Ok(vec![db_data(2, "Peter"), invalid])
+        });
+        let service = Service::new(Arc::new(repository));
+
+        let error = service
+            .get_all(2, 0, Sort::default(), false)
+            .await
+            .err()
+            .unwrap();
+        assert!(
+            matches!(error, Error::Repo(RepoError::InvalidRow(id, _)) if id == DataId::from(3))
+        );
+    }
+
+    #[actix_rt::test]
+    async fn get_random_maps_empty_table_to_not_found() {
+        let mut repository = MockRepository::new();
+        repository
+            .expect_get_random()
+            .returning(|| Err(RepoError::NotFound));
+        let service = Service::new(Arc::new(repository));
+
+        let error = service.get_random().await.err().unwrap();
+        assert!(matches!(error, Error::NotFound));
+    }
+
+    #[actix_rt::test]
+    async fn get_all_passes_paging_through() {
+        let mut repository = MockRepository::new();
+        repository
+            .expect_get_all()
+            .withf(|limit, offset, _, include_deleted| {
+                *limit == 2 && *offset == 1 && !include_deleted
+            })
+            .returning(|_, _, _, _| Ok(vec![db_data(2, "Peter"), db_data(3, "Santiago")]));
+        let service = Service::new(Arc::new(repository));
+
+        let data = service.get_all(2, 1, Sort::default(), false).await.unwrap();
+        let names = data.iter().map(|d| d.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["Peter", "Santiago"]);
+    }
+}
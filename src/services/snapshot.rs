@@ -0,0 +1,50 @@
+use crate::{
+    models::data::{Data, DataStats},
+    repositories::snapshot::ISnapshotRepository,
+    services::error::Error,
+};
+use async_trait::async_trait;
+use coi::Inject;
+use std::convert::TryFrom;
+use std::sync::Arc;
+use tracing::instrument;
+
+// Reads that share the request's snapshot, so calls made while handling one request agree with
+// each other even if rows change in between.
+#[async_trait]
+pub trait ISnapshotService: Inject {
+    async fn get_recent(&self, limit: i64) -> Result<Vec<Data>, Error>;
+    async fn stats(&self) -> Result<DataStats, Error>;
+}
+
+#[derive(Inject)]
+#[coi(provides pub dyn ISnapshotService with SnapshotService::new(snapshot_repository))]
+struct SnapshotService {
+    #[coi(inject)]
+    snapshot_repository: Arc<dyn ISnapshotRepository>,
+}
+
+#[async_trait]
+impl ISnapshotService for SnapshotService {
+    #[instrument(skip(self), err)]
+    async fn get_recent(&self, limit: i64) -> Result<Vec<Data>, Error> {
+        self.snapshot_repository
+            .get_recent(limit)
+            .await
+            .and_then(|v| v.into_iter().map(Data::try_from).collect())
+            .map_err(Into::into)
+    }
+
+    #[instrument(skip(self), err)]
+    async fn stats(&self) -> Result<DataStats, Error> {
+        self.snapshot_repository.stats().await.map_err(Into::into)
+    }
+}
+
+impl SnapshotService {
+    fn new(snapshot_repository: Arc<dyn ISnapshotRepository>) -> Self {
+        Self {
+            snapshot_repository,
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use crate::config::Config;
+use crate::postgres::{
+    build_pool, wait_for_database, warm_up, AppTls, PostgresPool, PostgresPoolProvider,
+};
+#[cfg(feature = "in-memory")]
+use crate::repositories::memory::InMemoryRepositoryProvider;
+use crate::repositories::repo::IRepository;
+#[cfg(not(any(feature = "in-memory", feature = "sqlx")))]
+use crate::repositories::repo::RepositoryProvider;
+#[cfg(all(feature = "sqlx", not(feature = "in-memory")))]
+use crate::repositories::sqlx::{build_sqlx_pool, SqlxRepositoryProvider};
+use coi::{Container, Provide};
+use mobc_postgres::mobc::Pool;
+use mobc_postgres::PgConnectionManager;
+
+// The providers for `pool` and `read_pool`, shared by every binary so they are built from `Config`
+// the same way. Without `READ_DATABASE_URL` both are backed by the same pool.
+pub struct Pools {
+    pub pool: PostgresPoolProvider<AppTls>,
+    pub read_pool: PostgresPoolProvider<AppTls>,
+    pub startup: PoolStartup,
+}
+
+pub fn build_pools(config: &Config) -> Pools {
+    let pool_config = &config.pool;
+    // Spelled out because `AppTls` is `Copy` without the `tls` feature and clippy would object.
+    let pool = build_pool(
+        config.database.clone(),
+        Clone::clone(&config.tls),
+        pool_config,
+    );
+    let read_pool = match &config.read_database {
+        Some(read_config) => {
+            build_pool(read_config.clone(), Clone::clone(&config.tls), pool_config)
+        }
+        None => pool.clone(),
+    };
+    let startup = if config.read_database.is_some() {
+        vec![("pool", pool.clone()), ("read_pool", read_pool.clone())]
+    } else {
+        vec![("pool", pool.clone())]
+    };
+    let (retry, breaker) = (config.pool_retry, config.pool_breaker);
+    Pools {
+        pool: PostgresPoolProvider::new("pool", pool, retry, breaker),
+        read_pool: PostgresPoolProvider::new("read_pool", read_pool, retry, breaker),
+        startup: PoolStartup(startup),
+    }
+}
+
+// Each distinct pool, to connect and warm up once the server is bound.
+pub struct PoolStartup(Vec<(&'static str, Pool<PgConnectionManager<AppTls>>)>);
+
+impl PoolStartup {
+    // Waits up to `DB_STARTUP_TIMEOUT` for each pool to connect, then opens its `POOL_WARMUP`
+    // connections. The in-memory repository never touches the database, so there is nothing to do.
+    pub async fn run(&self, config: &Config) -> Result<(), String> {
+        if cfg!(feature = "in-memory") {
+            return Ok(());
+        }
+        for (name, pool) in &self.0 {
+            wait_for_database(name, pool, config.db_startup_timeout)
+                .await
+                .map_err(|e| format!("Failed to connect {}: {}", name, e))?;
+            warm_up(name, pool, &config.pool)
+                .await
+                .map_err(|e| format!("Failed to warm up {}: {}", name, e))?;
+        }
+        Ok(())
+    }
+}
+
+// The `repository` for the enabled features: `in-memory` wins over `sqlx`, and tokio-postgres is
+// used when neither is enabled.
+#[cfg(feature = "in-memory")]
+pub fn repository_provider(
+    _config: &Config,
+) -> impl Provide<Output = dyn IRepository> + Send + Sync + 'static {
+    InMemoryRepositoryProvider::new()
+}
+
+#[cfg(all(feature = "sqlx", not(feature = "in-memory")))]
+pub fn repository_provider(
+    config: &Config,
+) -> impl Provide<Output = dyn IRepository> + Send + Sync + 'static {
+    SqlxRepositoryProvider::new(
+        build_sqlx_pool(&config.database, &config.pool, config.query_timeout),
+        build_sqlx_pool(
+            config.read_database.as_ref().unwrap_or(&config.database),
+            &config.pool,
+            config.query_timeout,
+        ),
+    )
+}
+
+#[cfg(not(any(feature = "in-memory", feature = "sqlx")))]
+pub fn repository_provider(
+    _config: &Config,
+) -> impl Provide<Output = dyn IRepository> + Send + Sync + 'static {
+    RepositoryProvider::<AppTls>::new()
+}
+
+// Closes the idle connections of `pool` and `read_pool`. Called once the server has stopped, before
+// the container holding them is dropped.
+pub async fn drain_pools(container: &Container) -> Result<(), String> {
+    for key in ["pool", "read_pool"] {
+        let pool = container
+            .resolve::<PostgresPool<AppTls>>(key)
+            .map_err(|e| format!("{}", e))?;
+        pool.drain().await;
+    }
+    tracing::info!("Database pools drained");
+    Ok(())
+}
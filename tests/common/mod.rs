@@ -0,0 +1,66 @@
+use coi::{container, Container};
+use coi_actix_sample::config::{Config, ConfigProvider};
+use coi_actix_sample::metrics::MetricsRegistryProvider;
+use coi_actix_sample::postgres::{build_pool, PostgresPoolProvider};
+use coi_actix_sample::repositories::repo::RepositoryProvider;
+use coi_actix_sample::repositories::slow_query::SlowQueryLogProvider;
+use coi_actix_sample::repositories::snapshot::{
+    RequestConnectionProvider, SnapshotRepositoryProvider,
+};
+use coi_actix_sample::services::snapshot::SnapshotServiceProvider;
+use mobc_postgres::tokio_postgres::{Config as PgConfig, NoTls};
+use std::sync::Arc;
+use testcontainers_modules::postgres::Postgres;
+use testcontainers_modules::testcontainers::runners::AsyncRunner;
+use testcontainers_modules::testcontainers::ContainerAsync;
+
+const INIT_SQL: &str = include_str!("../../xtask/src/sql/init.sql");
+const SEED_SQL: &str = include_str!("../../xtask/src/sql/seed.sql");
+
+// A throwaway Postgres container with the schema and sample rows loaded, plus a coi container
+// wired the same way as the server. Every test starts its own so they can run in parallel.
+pub struct TestDatabase {
+    pub container: Container,
+    _node: ContainerAsync<Postgres>,
+}
+
+pub async fn start() -> TestDatabase {
+    let node = Postgres::default()
+        .with_init_sql(INIT_SQL.as_bytes().to_vec())
+        .with_init_sql(SEED_SQL.as_bytes().to_vec())
+        .start()
+        .await
+        .expect("Failed to start postgres container");
+    let host = node.get_host().await.expect("Failed to get container host");
+    let port = node
+        .get_host_port_ipv4(5432)
+        .await
+        .expect("Failed to get container port");
+
+    let mut pg_config = PgConfig::new();
+    pg_config
+        .host(host.to_string())
+        .port(port)
+        .user("postgres")
+        .password("postgres")
+        .dbname("postgres");
+    let config = Config::from_env().expect("Invalid configuration");
+    let (retry, breaker) = (config.pool_retry, config.pool_breaker);
+    let pool = build_pool(pg_config, NoTls, &config.pool);
+
+    let container = container! {
+        config => ConfigProvider::new(Arc::new(config)); singleton,
+        pool => PostgresPoolProvider::new("pool", pool.clone(), retry, breaker); singleton,
+        read_pool => PostgresPoolProvider::new("read_pool", pool, retry, breaker); singleton,
+        registry => MetricsRegistryProvider; singleton,
+        repository => RepositoryProvider::<NoTls>::new(); scoped,
+        slow_queries => SlowQueryLogProvider; singleton,
+        request_connection => RequestConnectionProvider::<NoTls>::new(); scoped,
+        snapshot_repository => SnapshotRepositoryProvider::<NoTls>::new(); scoped,
+        snapshot => SnapshotServiceProvider; scoped,
+    };
+    TestDatabase {
+        container,
+        _node: node,
+    }
+}
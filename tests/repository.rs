@@ -0,0 +1,209 @@
+mod common;
+
+use coi_actix_sample::models::data::DataId;
+use coi_actix_sample::models::sort::Sort;
+use coi_actix_sample::postgres::PostgresPool;
+use coi_actix_sample::repositories::error::Error;
+use coi_actix_sample::repositories::repo::IRepository;
+use coi_actix_sample::services::snapshot::ISnapshotService;
+use mobc_postgres::tokio_postgres::NoTls;
+use std::time::Duration;
+
+#[actix_rt::test]
+#[ignore = "requires docker"]
+async fn get_returns_seeded_row() {
+    let db = common::start().await;
+    let repository = db
+        .container
+        .resolve::<dyn IRepository>("repository")
+        .unwrap();
+
+    let data = repository.get(DataId::from(1), false).await.unwrap();
+    assert_eq!(data.id, DataId::from(1));
+    assert_eq!(data.name, "Paul");
+}
+
+#[actix_rt::test]
+#[ignore = "requires docker"]
+async fn get_missing_row_is_not_found() {
+    let db = common::start().await;
+    let repository = db
+        .container
+        .resolve::<dyn IRepository>("repository")
+        .unwrap();
+
+    let error = repository
+        .get(DataId::from(1000), false)
+        .await
+        .err()
+        .unwrap();
+    assert!(matches!(error, Error::NotFound));
+}
+
+#[actix_rt::test]
+#[ignore = "requires docker"]
+async fn get_all_returns_every_seeded_row() {
+    let db = common::start().await;
+    let repository = db
+        .container
+        .resolve::<dyn IRepository>("repository")
+        .unwrap();
+
+    let data = repository
+        .get_all(50, 0, Sort::default(), false)
+        .await
+        .unwrap();
+    let names = data.iter().map(|d| d.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, ["Paul", "Peter", "Santiago", "Oliver"]);
+}
+
+#[actix_rt::test]
+#[ignore = "requires docker"]
+async fn get_all_applies_limit_and_offset() {
+    let db = common::start().await;
+    let repository = db
+        .container
+        .resolve::<dyn IRepository>("repository")
+        .unwrap();
+
+    let data = repository
+        .get_all(2, 1, Sort::default(), false)
+        .await
+        .unwrap();
+    let ids = data.iter().map(|d| i64::from(d.id)).collect::<Vec<_>>();
+    assert_eq!(ids, [2, 3]);
+}
+
+#[actix_rt::test]
+#[ignore = "requires docker"]
+async fn delete_hides_row_unless_deleted_are_included() {
+    let db = common::start().await;
+    let repository = db
+        .container
+        .resolve::<dyn IRepository>("repository")
+        .unwrap();
+
+    assert_eq!(repository.delete(DataId::from(1)).await.unwrap(), 1);
+    assert_eq!(repository.delete(DataId::from(1)).await.unwrap(), 0);
+    let error = repository.get(DataId::from(1), false).await.err().unwrap();
+    assert!(matches!(error, Error::NotFound));
+    let data = repository.get(DataId::from(1), true).await.unwrap();
+    assert!(data.deleted_at.is_some());
+    let data = repository
+        .get_all(50, 0, Sort::default(), false)
+        .await
+        .unwrap();
+    assert!(data.iter().all(|d| d.id != DataId::from(1)));
+}
+
+#[actix_rt::test]
+#[ignore = "requires docker"]
+async fn insert_trims_and_limits_names() {
+    let db = common::start().await;
+    let repository = db
+        .container
+        .resolve::<dyn IRepository>("repository")
+        .unwrap();
+
+    let data = repository.insert("  Trimmed \n").await.unwrap();
+    assert_eq!(data.name, "Trimmed");
+
+    let error = repository.insert(&"x".repeat(256)).await.err().unwrap();
+    assert!(matches!(error, Error::InvalidName(_)));
+    let error = repository.update(data.id, " ").await.err().unwrap();
+    assert!(matches!(error, Error::InvalidName(_)));
+}
+
+#[actix_rt::test]
+#[ignore = "requires docker"]
+async fn overview_reads_share_a_snapshot() {
+    let db = common::start().await;
+    let pool = db
+        .container
+        .resolve::<PostgresPool<NoTls>>("read_pool")
+        .unwrap();
+    let repository = db
+        .container
+        .resolve::<dyn IRepository>("repository")
+        .unwrap();
+
+    // Resolved the way `/overview` resolves it, from a container scoped to one request.
+    let request = db.container.scoped();
+    let snapshot = request.resolve::<dyn ISnapshotService>("snapshot").unwrap();
+    let stats = snapshot.stats().await.unwrap();
+    assert_eq!(pool.state().await.in_use(), 1);
+
+    // Committed on another connection after the first read, so only a fresh snapshot would see it.
+    repository.insert("Mary").await.unwrap();
+
+    let recent = snapshot.get_recent(50).await.unwrap();
+    assert_eq!(recent.len() as i64, stats.count);
+    assert_eq!(pool.state().await.in_use(), 1);
+
+    // The rollback is spawned by the drop, so give it a moment to hand the connection back.
+    drop(snapshot);
+    drop(request);
+    for _ in 0..50 {
+        if pool.state().await.in_use() == 0 {
+            break;
+        }
+        actix_rt::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(pool.state().await.in_use(), 0);
+
+    let client = pool.get().await.unwrap();
+    let open = client
+        .query_one(
+            "SELECT count(*) FROM pg_stat_activity WHERE state = 'idle in transaction'",
+            &[],
+        )
+        .await
+        .unwrap()
+        .get::<_, i64>(0);
+    assert_eq!(open, 0);
+    let recent = repository.get_recent(50).await.unwrap();
+    assert_eq!(recent.len() as i64, stats.count + 1);
+}
+
+#[actix_rt::test]
+#[ignore = "requires docker"]
+async fn search_uses_name_trigram_index() {
+    let db = common::start().await;
+    let pool = db.container.resolve::<PostgresPool<NoTls>>("pool").unwrap();
+    let repository = db
+        .container
+        .resolve::<dyn IRepository>("repository")
+        .unwrap();
+
+    // Random-looking names, so a substring only matches a few rows and the index is worth using.
+    let client = pool.get().await.unwrap();
+    client
+        .batch_execute(
+            "INSERT INTO data (name) SELECT md5(g::text) FROM generate_series(1, 20000) g; \
+             ANALYZE data;",
+        )
+        .await
+        .unwrap();
+    let term = client
+        .query_one("SELECT substr(md5('1234'), 5, 10)", &[])
+        .await
+        .unwrap()
+        .get::<_, String>(0);
+
+    let plan = client
+        .query(
+            "EXPLAIN SELECT id, name, created_at, updated_at, deleted_at FROM data \
+             WHERE name ILIKE $1 AND deleted_at IS NULL LIMIT 50",
+            &[&format!("%{}%", term)],
+        )
+        .await
+        .unwrap()
+        .iter()
+        .map(|row| row.get::<_, String>(0))
+        .collect::<Vec<_>>()
+        .join("\n");
+    assert!(plan.contains("data_name_trgm_idx"), "{}", plan);
+
+    let data = repository.search(&term).await.unwrap();
+    assert!(data.iter().any(|d| d.name.contains(&term)));
+}
@@ -1,5 +1,11 @@
+use bollard::container::{ListContainersOptions, LogOutput, LogsOptions, RemoveContainerOptions};
+use bollard::errors::Error as DockerError;
+use bollard::models::ContainerSummary;
 use bollard::Docker;
-use std::io::{self, ErrorKind};
+use futures_util::stream::StreamExt;
+use std::fs;
+use std::io::{self, ErrorKind, IsTerminal, Write};
+use std::path::{Path, PathBuf};
 use std::process::{Child, Command, ExitStatus};
 use std::time::Duration;
 use structopt::StructOpt;
@@ -12,7 +18,16 @@ use tokio_postgres::{connect, Client, Error as PostgresError, NoTls};
 build, run, init, seed
 
 Once init or seed has been run, you can just call the run subcommand and reuse
-the existing data."
+the existing data. Use the stop subcommand to shut down the running container,
+or clean to remove both the container and the image. The status subcommand
+reports on all of the above, and reset drops and reseeds the database. Logs
+follows the running container's output until interrupted, and serve starts the
+container if needed and then runs the server against it. Psql opens an
+interactive session in the running container, passing any arguments after `--`
+on to psql.
+
+Migrate applies any pending files from the migrations directory, which must be
+named `<version>_<description>.sql`."
 )]
 enum Step {
     #[structopt(about = "Build the postgres docker image")]
@@ -22,7 +37,41 @@ enum Step {
     #[structopt(about = "Initialize the database in the postgres docker image")]
     Init,
     #[structopt(about = "Seed dummy data to the postgres docker image")]
-    Seed,
+    Seed {
+        #[structopt(
+            long,
+            help = "Generate this many rows with random names instead of the sample data"
+        )]
+        count: Option<u64>,
+    },
+    #[structopt(about = "Stop the running postgres docker container")]
+    Stop,
+    #[structopt(about = "Remove the postgres docker container and image")]
+    Clean,
+    #[structopt(about = "Report whether the image, container and database are available")]
+    Status,
+    #[structopt(about = "Drop all tables then reinitialize and reseed the database")]
+    Reset,
+    #[structopt(about = "Apply pending migrations from the migrations directory")]
+    Migrate,
+    #[structopt(about = "Follow the running postgres docker container's logs")]
+    Logs {
+        #[structopt(long, help = "Only show this many existing lines before following")]
+        tail: Option<u64>,
+    },
+    #[structopt(about = "Start the postgres docker container if needed, then run the server")]
+    Serve {
+        #[structopt(long, help = "Build and run the server in release mode")]
+        release: bool,
+    },
+    #[structopt(about = "Open a psql session in the running postgres docker container")]
+    Psql {
+        #[structopt(
+            last = true,
+            help = "Extra arguments for psql, e.g. `-- -c 'SELECT 1'`"
+        )]
+        args: Vec<String>,
+    },
 }
 
 #[derive(Error, Debug)]
@@ -39,6 +88,18 @@ enum XtaskError {
     #[error("Command `{0}` did not exit: {1}")]
     Exit(String, ExitStatus),
 
+    #[error("Invalid {0} `{1}`: expected a port number")]
+    InvalidPort(&'static str, String),
+
+    #[error("Invalid migration file name `{0}`: expected `<version>_<description>.sql`")]
+    InvalidMigration(String),
+
+    #[error("Database is not healthy")]
+    Unhealthy,
+
+    #[error("No running {0} container found, start one with `cargo xtask run`")]
+    NotRunning(&'static str),
+
     #[error("Uknown error: {0}")]
     Unknown(String),
 }
@@ -48,6 +109,22 @@ type Result<T> = std::result::Result<T, XtaskError>;
 const DOCKER_COMMAND: &str = "docker";
 // const DOCKER_URI: &str = "unix:///var/run/docker.sock";
 const DOCKER_IMAGE_NAME: &str = "coi-actix-sample-postgres";
+const PG_PORT_VAR: &str = "COI_PG_PORT";
+const DEFAULT_PG_PORT: u16 = 45432;
+const PG_USER: &str = "docker";
+const PG_PASSWORD: &str = "docker";
+const PG_DATABASE: &str = "docker";
+const READY_ATTEMPTS: u32 = 30;
+const READY_INTERVAL: Duration = Duration::from_secs(1);
+
+fn pg_port() -> Result<u16> {
+    match std::env::var(PG_PORT_VAR) {
+        Ok(port) => port
+            .parse()
+            .map_err(|_| XtaskError::InvalidPort(PG_PORT_VAR, port)),
+        Err(_) => Ok(DEFAULT_PG_PORT),
+    }
+}
 
 fn build_step() -> Result<()> {
     let mut command = build()?;
@@ -98,7 +175,7 @@ async fn init_step() -> Result<()> {
     }
 }
 
-async fn seed_step() -> Result<()> {
+async fn seed_step(count: Option<u64>) -> Result<()> {
     let docker = Docker::connect_with_local_defaults()?;
     let containers = docker.list_containers::<String>(None).await?;
     let container = containers
@@ -107,7 +184,7 @@ async fn seed_step() -> Result<()> {
     if let Some(_container) = container {
         let mut client = make_client().await?;
         init_db(&mut client).await?;
-        seed(&mut client).await
+        seed(&mut client, count).await
     } else {
         let images = docker.list_images::<String>(None).await?;
         if !images.iter().any(|i| i.id == DOCKER_IMAGE_NAME) {
@@ -118,7 +195,7 @@ async fn seed_step() -> Result<()> {
             tokio::time::sleep(Duration::from_secs(5)).await;
             let mut client = make_client().await?;
             init_db(&mut client).await?;
-            seed(&mut client).await
+            seed(&mut client, count).await
         }
         .await
         {
@@ -130,6 +207,272 @@ async fn seed_step() -> Result<()> {
     }
 }
 
+async fn stop_step() -> Result<()> {
+    let docker = Docker::connect_with_local_defaults()?;
+    match find_container(&docker).await?.and_then(|c| c.id) {
+        Some(id) => {
+            docker.stop_container(&id, None).await?;
+            println!("Stopped container {}", id);
+        }
+        None => println!("No running {} container found", DOCKER_IMAGE_NAME),
+    }
+    Ok(())
+}
+
+async fn clean_step() -> Result<()> {
+    let docker = Docker::connect_with_local_defaults()?;
+    let options = ListContainersOptions::<String> {
+        all: true,
+        ..Default::default()
+    };
+    let containers = docker.list_containers(Some(options)).await?;
+    let mut removed = false;
+    for container in containers
+        .into_iter()
+        .filter(|c| c.image.as_deref() == Some(DOCKER_IMAGE_NAME))
+    {
+        let id = match container.id {
+            Some(id) => id,
+            None => continue,
+        };
+        if container.state.as_deref() == Some("running") {
+            docker.stop_container(&id, None).await?;
+            println!("Stopped container {}", id);
+        }
+        let options = RemoveContainerOptions {
+            force: true,
+            ..Default::default()
+        };
+        docker.remove_container(&id, Some(options)).await?;
+        println!("Removed container {}", id);
+        removed = true;
+    }
+    if !removed {
+        println!("No {} containers found", DOCKER_IMAGE_NAME);
+    }
+
+    match docker.remove_image(DOCKER_IMAGE_NAME, None, None).await {
+        Ok(_) => println!("Removed image {}", DOCKER_IMAGE_NAME),
+        Err(DockerError::DockerResponseServerError {
+            status_code: 404, ..
+        }) => println!("No {} image found", DOCKER_IMAGE_NAME),
+        Err(e) => return Err(e.into()),
+    }
+    Ok(())
+}
+
+async fn status_step() -> Result<()> {
+    let (image, container) = match docker_status().await {
+        Ok((image, container)) => {
+            println!("image:     {}", if image { "present" } else { "missing" });
+            match container.as_ref().and_then(|c| c.id.as_deref()) {
+                Some(id) => println!("container: running ({})", id),
+                None => println!("container: not running"),
+            }
+            (image, container.is_some())
+        }
+        Err(e) => {
+            println!("docker:    unreachable ({})", e);
+            (false, false)
+        }
+    };
+
+    let database = match make_client().await {
+        Ok(_) => {
+            println!("database:  accepting connections");
+            true
+        }
+        Err(e) => {
+            println!("database:  unreachable ({})", e);
+            false
+        }
+    };
+
+    if image && container && database {
+        Ok(())
+    } else {
+        Err(XtaskError::Unhealthy)
+    }
+}
+
+async fn logs_step(tail: Option<u64>) -> Result<()> {
+    let docker = Docker::connect_with_local_defaults()?;
+    let id = match find_container(&docker).await?.and_then(|c| c.id) {
+        Some(id) => id,
+        None => {
+            println!("No running {} container found", DOCKER_IMAGE_NAME);
+            return Ok(());
+        }
+    };
+    let options = LogsOptions::<String> {
+        follow: true,
+        stdout: true,
+        stderr: true,
+        tail: tail.map_or_else(|| "all".to_owned(), |n| n.to_string()),
+        ..Default::default()
+    };
+    // Ends when the container stops, otherwise runs until the process is interrupted.
+    let mut logs = docker.logs(&id, Some(options));
+    while let Some(output) = logs.next().await {
+        match output? {
+            LogOutput::StdErr { message } => io::stderr().write_all(&message)?,
+            output => io::stdout().write_all(&output.into_bytes())?,
+        }
+    }
+    Ok(())
+}
+
+async fn psql_step(args: Vec<String>) -> Result<()> {
+    let docker = Docker::connect_with_local_defaults()?;
+    let id = find_container(&docker)
+        .await?
+        .and_then(|c| c.id)
+        .ok_or(XtaskError::NotRunning(DOCKER_IMAGE_NAME))?;
+    let mut command = psql(&id, &args)?;
+    success_check(command.wait(), DOCKER_COMMAND)
+}
+
+async fn serve_step(release: bool) -> Result<()> {
+    let (image, container) = docker_status().await?;
+    let mut database = None;
+    if container.is_none() {
+        if !image {
+            build_step()?;
+        }
+        database = Some(run()?);
+    }
+    let ready = async {
+        let mut client = wait_for_client().await?;
+        init_db(&mut client).await
+    }
+    .await;
+    if let Err(e) = ready {
+        if let Some(mut database) = database {
+            database.kill()?;
+        }
+        return Err(e);
+    }
+    // The container is left running after the server exits, like the run subcommand.
+    let mut server = serve(release)?;
+    success_check(server.wait(), "cargo")
+}
+
+// A freshly started container takes a few seconds before it accepts connections.
+async fn wait_for_client() -> Result<Client> {
+    let mut attempt = 1;
+    loop {
+        match make_client().await {
+            Ok(client) => return Ok(client),
+            Err(_) if attempt < READY_ATTEMPTS => {
+                attempt += 1;
+                tokio::time::sleep(READY_INTERVAL).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+async fn docker_status() -> Result<(bool, Option<ContainerSummary>)> {
+    let docker = Docker::connect_with_local_defaults()?;
+    let images = docker.list_images::<String>(None).await?;
+    let image = images.iter().any(|i| {
+        i.repo_tags
+            .iter()
+            .any(|t| t == &format!("{}:latest", DOCKER_IMAGE_NAME))
+    });
+    let container = find_container(&docker).await?;
+    Ok((image, container))
+}
+
+async fn reset_step() -> Result<()> {
+    let mut client = make_client().await?;
+    // Run everything in one transaction so a failure leaves the previous schema intact.
+    let transaction = client.transaction().await?;
+    transaction
+        .batch_execute(include_str!("sql/reset.sql"))
+        .await?;
+    transaction
+        .batch_execute(include_str!("sql/init.sql"))
+        .await?;
+    transaction
+        .batch_execute(include_str!("sql/seed.sql"))
+        .await?;
+    transaction.commit().await?;
+    println!("Database reset");
+    Ok(())
+}
+
+struct Migration {
+    version: i64,
+    name: String,
+    path: PathBuf,
+}
+
+fn migrations_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../migrations")
+}
+
+fn read_migrations(dir: &Path) -> Result<Vec<Migration>> {
+    let mut migrations = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("sql") {
+            continue;
+        }
+        let name = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or_default()
+            .to_owned();
+        let version = name
+            .split('_')
+            .next()
+            .and_then(|v| v.parse().ok())
+            .ok_or_else(|| XtaskError::InvalidMigration(name.clone()))?;
+        migrations.push(Migration {
+            version,
+            name,
+            path,
+        });
+    }
+    migrations.sort_by_key(|m| m.version);
+    Ok(migrations)
+}
+
+async fn migrate_step() -> Result<()> {
+    let migrations = read_migrations(&migrations_dir())?;
+    let mut client = make_client().await?;
+    client
+        .batch_execute(include_str!("sql/schema_migrations.sql"))
+        .await?;
+    let applied = client
+        .query("SELECT version FROM schema_migrations", &[])
+        .await?
+        .into_iter()
+        .map(|row| row.get::<_, i64>(0))
+        .collect::<Vec<_>>();
+
+    let mut ran = 0;
+    for migration in migrations.iter().filter(|m| !applied.contains(&m.version)) {
+        let sql = fs::read_to_string(&migration.path)?;
+        let transaction = client.transaction().await?;
+        transaction.batch_execute(&sql).await?;
+        transaction
+            .execute(
+                "INSERT INTO schema_migrations (version, name) VALUES ($1, $2)",
+                &[&migration.version, &migration.name],
+            )
+            .await?;
+        transaction.commit().await?;
+        println!("Applied migration {}", migration.name);
+        ran += 1;
+    }
+    if ran == 0 {
+        println!("No pending migrations");
+    }
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     env_logger::init();
@@ -138,10 +481,25 @@ async fn main() -> Result<()> {
         Step::Build => build_step(),
         Step::Run => run_step().await,
         Step::Init => init_step().await,
-        Step::Seed => seed_step().await,
+        Step::Seed { count } => seed_step(count).await,
+        Step::Stop => stop_step().await,
+        Step::Clean => clean_step().await,
+        Step::Status => status_step().await,
+        Step::Reset => reset_step().await,
+        Step::Migrate => migrate_step().await,
+        Step::Logs { tail } => logs_step(tail).await,
+        Step::Serve { release } => serve_step(release).await,
+        Step::Psql { args } => psql_step(args).await,
     }
 }
 
+async fn find_container(docker: &Docker) -> Result<Option<ContainerSummary>> {
+    let containers = docker.list_containers::<String>(None).await?;
+    Ok(containers
+        .into_iter()
+        .find(|c| c.image.as_deref() == Some(DOCKER_IMAGE_NAME)))
+}
+
 fn check_not_found(command: &str) -> impl Fn(io::Error) -> io::Error + '_ {
     move |e| {
         if e.kind() == ErrorKind::NotFound {
@@ -184,23 +542,73 @@ fn build() -> Result<Child> {
 }
 
 fn run() -> Result<Child> {
+    let port = pg_port()?;
     println!("Running docker");
     Command::new("docker")
         .arg("run")
         .arg("-p")
-        .arg("45432:5432")
+        .arg(format!("{}:5432", port))
         .arg(DOCKER_IMAGE_NAME)
         .spawn()
         .map_err(check_not_found("docker"))
         .map_err(Into::into)
 }
 
+// Runs the container's own psql, so it doesn't need to be installed locally, with the same
+// credentials as `make_client`.
+fn psql(id: &str, args: &[String]) -> Result<Child> {
+    let mut command = Command::new(DOCKER_COMMAND);
+    command.arg("exec").arg("-i");
+    // Docker refuses to allocate a terminal when input is piped in, e.g. `< script.sql`.
+    if io::stdin().is_terminal() {
+        command.arg("-t");
+    }
+    command
+        .arg("-e")
+        .arg(format!("PGPASSWORD={}", PG_PASSWORD))
+        .arg(id)
+        .arg("psql")
+        .arg("-h")
+        .arg("127.0.0.1")
+        .arg("-U")
+        .arg(PG_USER)
+        .arg(PG_DATABASE)
+        .args(args)
+        .spawn()
+        .map_err(check_not_found("docker"))
+        .map_err(Into::into)
+}
+
+// Runs the server from the workspace root, pointed at the container's port unless `PGPORT` is
+// already set. Its output goes straight to this terminal.
+fn serve(release: bool) -> Result<Child> {
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_owned());
+    let mut command = Command::new(&cargo);
+    command
+        .current_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join(".."))
+        .arg("run");
+    if release {
+        command.arg("--release");
+    }
+    if std::env::var_os("PGPORT").is_none() {
+        command.env("PGPORT", pg_port()?.to_string());
+    }
+    println!("Running server");
+    command
+        .spawn()
+        .map_err(check_not_found("cargo"))
+        .map_err(Into::into)
+}
+
 async fn make_client() -> Result<Client> {
-    let (client, connection) = connect(
-        "host=127.0.0.1 dbname=docker port=45432 user=docker password=docker",
-        NoTls,
-    )
-    .await?;
+    let config = format!(
+        "host=127.0.0.1 dbname={} port={} user={} password={}",
+        PG_DATABASE,
+        pg_port()?,
+        PG_USER,
+        PG_PASSWORD
+    );
+    let (client, connection) = connect(&config, NoTls).await?;
     tokio::spawn(async move {
         if let Err(e) = connection.await {
             eprintln!("connection error: {}", e);
@@ -216,9 +624,72 @@ async fn init_db(client: &mut Client) -> Result<()> {
         .map_err(Into::into)
 }
 
-async fn seed(client: &mut Client) -> Result<()> {
-    client
-        .batch_execute(include_str!("sql/seed.sql"))
-        .await
-        .map_err(Into::into)
+async fn seed(client: &mut Client, count: Option<u64>) -> Result<()> {
+    match count {
+        Some(count) => seed_generated(client, count).await,
+        None => client
+            .batch_execute(include_str!("sql/seed.sql"))
+            .await
+            .map_err(Into::into),
+    }
+}
+
+const SEED_BATCH_SIZE: u64 = 1000;
+const ADJECTIVES: &[&str] = &[
+    "Amber", "Brave", "Calm", "Daring", "Eager", "Fuzzy", "Gentle", "Happy", "Icy", "Jolly",
+    "Kind", "Lucky", "Mellow", "Nimble", "Odd", "Proud", "Quiet", "Rapid", "Sunny", "Tidy",
+];
+const NOUNS: &[&str] = &[
+    "Otter", "Falcon", "Badger", "Heron", "Lynx", "Marmot", "Newt", "Osprey", "Panda", "Quail",
+    "Raven", "Salmon", "Tapir", "Urchin", "Vole", "Walrus", "Yak", "Zebra", "Bison", "Crane",
+];
+
+// xorshift64, seeded from the clock; good enough for varied sample names. Names end with the row
+// number so they rarely collide with the unique `data.name`.
+struct NameGenerator(u64);
+
+impl NameGenerator {
+    fn new() -> Self {
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+        Self(seed | 1)
+    }
+
+    fn next(&mut self, n: u64) -> String {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        let value = self.0 as usize;
+        format!(
+            "{} {} {}",
+            ADJECTIVES[value % ADJECTIVES.len()],
+            NOUNS[(value / ADJECTIVES.len()) % NOUNS.len()],
+            n
+        )
+    }
+}
+
+// Inserts `count` generated rows, `SEED_BATCH_SIZE` at a time, in a single transaction. Names that
+// already exist (e.g. from an earlier run) are skipped.
+async fn seed_generated(client: &mut Client, count: u64) -> Result<()> {
+    let mut names = NameGenerator::new();
+    let transaction = client.transaction().await?;
+    let statement = transaction
+        .prepare("INSERT INTO data (name) SELECT unnest($1::TEXT[]) ON CONFLICT (name) DO NOTHING")
+        .await?;
+    let mut generated = 0;
+    let mut inserted = 0;
+    while generated < count {
+        let batch = SEED_BATCH_SIZE.min(count - generated);
+        let batch_names = (generated..generated + batch)
+            .map(|n| names.next(n))
+            .collect::<Vec<_>>();
+        inserted += transaction.execute(&statement, &[&batch_names]).await?;
+        generated += batch;
+    }
+    transaction.commit().await?;
+    println!("Inserted {} rows", inserted);
+    Ok(())
 }
@@ -1,4 +1,48 @@
 CREATE TABLE IF NOT EXISTS data (
     id BIGSERIAL PRIMARY KEY,
-    name TEXT NOT NULL
+    name TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    deleted_at TIMESTAMPTZ,
+    CONSTRAINT data_name_length CHECK (char_length(name) <= 255)
 );
+
+-- Lets `name ILIKE '%term%'` searches use an index instead of scanning every row.
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+CREATE INDEX IF NOT EXISTS data_name_trgm_idx ON data USING GIN (name gin_trgm_ops);
+
+-- Serves the newest-first ordering of `GET /api/v1/data/recent`.
+CREATE INDEX IF NOT EXISTS data_created_at_idx ON data (created_at DESC, id DESC);
+
+-- Tells listening servers which row changed so they can drop it from their caches. `TRUNCATE`
+-- sends an empty payload, meaning every row.
+CREATE OR REPLACE FUNCTION notify_data_changed() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'TRUNCATE' THEN
+        PERFORM pg_notify('data_changed', '');
+    ELSE
+        PERFORM pg_notify('data_changed', OLD.id::text);
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS data_changed ON data;
+CREATE TRIGGER data_changed AFTER UPDATE OR DELETE ON data
+    FOR EACH ROW EXECUTE FUNCTION notify_data_changed();
+DROP TRIGGER IF EXISTS data_truncated ON data;
+CREATE TRIGGER data_truncated AFTER TRUNCATE ON data
+    FOR EACH STATEMENT EXECUTE FUNCTION notify_data_changed();
+
+-- Tells listening servers about new rows so they can stream them to `GET /api/v1/data/events`
+-- subscribers. Only the id is sent, since a payload is limited to 8000 bytes.
+CREATE OR REPLACE FUNCTION notify_data_inserted() RETURNS trigger AS $$
+BEGIN
+    PERFORM pg_notify('data_inserted', NEW.id::text);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS data_inserted ON data;
+CREATE TRIGGER data_inserted AFTER INSERT ON data
+    FOR EACH ROW EXECUTE FUNCTION notify_data_inserted();